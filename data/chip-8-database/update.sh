@@ -0,0 +1,21 @@
+#!/bin/sh
+# Replaces the bundled programs.json with the upstream one, with its licence.
+# With --check, only fails if the bundled copy differs from upstream.
+set -eu
+cd "$(dirname "$0")"
+checkout=$(mktemp -d)
+trap 'rm -rf "$checkout"' EXIT
+git clone --quiet --depth 1 https://github.com/chip-8/chip-8-database "$checkout"
+if [ "${1:-}" = --check ]; then
+    status=0
+    for file in database/programs.json LICENSE; do
+        if ! cmp -s "$checkout/$file" "$(basename "$file")"; then
+            echo "$(basename "$file") differs from upstream, run $0 and commit the result" >&2
+            status=1
+        fi
+    done
+    exit $status
+fi
+cp "$checkout/database/programs.json" programs.json
+cp "$checkout/LICENSE" LICENSE
+git -C "$checkout" log -1 --format='Copied from chip-8/chip-8-database at %H' > SOURCE
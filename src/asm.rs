@@ -0,0 +1,753 @@
+// Assembler for the Octo dialect the disassembler writes. Source is a stream of
+// whitespace separated tokens, so several statements may share a line:
+//
+//     : main
+//         v0 := 0x05        # comments run to the end of the line
+//         i := sprite
+//         sprite v0 v0 5
+//         if v0 != 0 then jump main
+//     : sprite
+//         0xF0 0x90 0xF0
+//
+// Supported directives are `: label`, `:const NAME VALUE`, `:byte VALUE`,
+// `:org ADDR`, `:call ADDR` and `:include "file"`. Bare numbers are emitted as
+// bytes. Unlike Octo, there is no implicit jump to `main`: bytes are laid out
+// from the load address in the order they are written.
+use crate::chip8::{Instruction, Platform, START_ADDR};
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+// Guards against files that include themselves
+const MAX_INCLUDE_DEPTH: usize = 16;
+const MEMORY_END: usize = 0x10000;
+// Words that start a statement, so they can't name a label or a constant
+const KEYWORDS: [&str; 25] = [
+    "clear",
+    "return",
+    "exit",
+    "lores",
+    "hires",
+    "scroll-right",
+    "scroll-left",
+    "scroll-down",
+    "scroll-up",
+    "audio",
+    "plane",
+    "jump",
+    "jump0",
+    "bcd",
+    "save",
+    "load",
+    "saveflags",
+    "loadflags",
+    "sprite",
+    "if",
+    "then",
+    "i",
+    "delay",
+    "buzzer",
+    "pitch",
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AsmError {
+    pub file: String,
+    pub line: usize,
+    pub col: usize,
+    pub message: String,
+}
+
+impl fmt::Display for AsmError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}:{}:{}: {}",
+            self.file, self.line, self.col, self.message
+        )
+    }
+}
+
+impl std::error::Error for AsmError {}
+
+// Assemble `source` for `platform`. Includes are looked up relative to the
+// current directory.
+pub fn assemble(source: &str, platform: Platform) -> Result<Vec<u8>, AsmError> {
+    let mut assembler = Assembler::new(platform);
+    let tokens = assembler.tokenize(source, "<input>".to_string(), Path::new("."), 0)?;
+    assembler.run(&tokens)
+}
+
+// Assemble the file at `path`. Includes are looked up relative to the including file.
+pub fn assemble_file(path: &Path, platform: Platform) -> Result<Vec<u8>, AsmError> {
+    let source = fs::read_to_string(path).map_err(|err| AsmError {
+        file: path.display().to_string(),
+        line: 1,
+        col: 1,
+        message: err.to_string(),
+    })?;
+    let mut assembler = Assembler::new(platform);
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let tokens = assembler.tokenize(&source, path.display().to_string(), dir, 0)?;
+    assembler.run(&tokens)
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    text: String,
+    file: usize,
+    line: usize,
+    col: usize,
+}
+
+// A reference to a label that may not be defined yet, patched once every label is known
+#[derive(Debug)]
+struct Fixup {
+    offset: usize,
+    wide: bool, // The 16 bit word of F000 NNNN rather than the 12 bit NNN of an opcode
+    token: Token,
+}
+
+struct Assembler {
+    platform: Platform,
+    files: Vec<String>,
+    origin: usize,
+    pos: usize,
+    rom: Vec<u8>,
+    written: bool,
+    labels: HashMap<String, u16>,
+    constants: HashMap<String, i64>,
+    fixups: Vec<Fixup>,
+}
+
+enum Target {
+    Address(u16),
+    Label(Token),
+}
+
+impl Assembler {
+    fn new(platform: Platform) -> Self {
+        Self {
+            platform,
+            files: Vec::new(),
+            origin: START_ADDR as usize,
+            pos: START_ADDR as usize,
+            rom: Vec::new(),
+            written: false,
+            labels: HashMap::new(),
+            constants: HashMap::new(),
+            fixups: Vec::new(),
+        }
+    }
+
+    fn error(&self, token: &Token, message: impl Into<String>) -> AsmError {
+        AsmError {
+            file: self.files[token.file].clone(),
+            line: token.line,
+            col: token.col,
+            message: message.into(),
+        }
+    }
+
+    fn tokenize(
+        &mut self,
+        source: &str,
+        name: String,
+        dir: &Path,
+        depth: usize,
+    ) -> Result<Vec<Token>, AsmError> {
+        let file = self.files.len();
+        self.files.push(name);
+        let mut tokens = Vec::new();
+        for (line_idx, line) in source.lines().enumerate() {
+            let chars: Vec<char> = line.chars().collect();
+            let mut idx = 0;
+            while idx < chars.len() {
+                if chars[idx].is_whitespace() {
+                    idx += 1;
+                    continue;
+                }
+                if chars[idx] == '#' {
+                    break;
+                }
+                let start = idx;
+                if chars[idx] == '"' {
+                    idx += 1;
+                    while idx < chars.len() && chars[idx] != '"' {
+                        idx += 1;
+                    }
+                    if idx == chars.len() {
+                        return Err(AsmError {
+                            file: self.files[file].clone(),
+                            line: line_idx + 1,
+                            col: start + 1,
+                            message: "unterminated string".to_string(),
+                        });
+                    }
+                    idx += 1;
+                } else {
+                    while idx < chars.len() && !chars[idx].is_whitespace() {
+                        idx += 1;
+                    }
+                }
+                tokens.push(Token {
+                    text: chars[start..idx].iter().collect(),
+                    file,
+                    line: line_idx + 1,
+                    col: start + 1,
+                });
+            }
+        }
+
+        // Splice included files in place of their :include directive
+        let mut out = Vec::with_capacity(tokens.len());
+        let mut tokens = tokens.into_iter();
+        while let Some(token) = tokens.next() {
+            if token.text != ":include" {
+                out.push(token);
+                continue;
+            }
+            let Some(path_token) = tokens.next() else {
+                return Err(self.error(&token, ":include expects a quoted file name"));
+            };
+            let Some(relative) = path_token
+                .text
+                .strip_prefix('"')
+                .and_then(|t| t.strip_suffix('"'))
+            else {
+                return Err(self.error(&path_token, ":include expects a quoted file name"));
+            };
+            if depth >= MAX_INCLUDE_DEPTH {
+                return Err(self.error(&path_token, "includes are nested too deeply"));
+            }
+            let path: PathBuf = dir.join(relative);
+            let source = fs::read_to_string(&path).map_err(|err| {
+                self.error(
+                    &path_token,
+                    format!("unable to include {}: {}", path.display(), err),
+                )
+            })?;
+            let included_dir = path.parent().unwrap_or(dir).to_path_buf();
+            out.extend(self.tokenize(
+                &source,
+                path.display().to_string(),
+                &included_dir,
+                depth + 1,
+            )?);
+        }
+        Ok(out)
+    }
+
+    fn run(mut self, tokens: &[Token]) -> Result<Vec<u8>, AsmError> {
+        let mut cursor = Cursor { tokens, idx: 0 };
+        while let Some(token) = cursor.next() {
+            self.statement(token, &mut cursor)?;
+        }
+        for fixup in std::mem::take(&mut self.fixups) {
+            let Some(&addr) = self.labels.get(&fixup.token.text) else {
+                return Err(self.error(
+                    &fixup.token,
+                    format!("undefined label '{}'", fixup.token.text),
+                ));
+            };
+            if fixup.wide {
+                self.rom[fixup.offset..fixup.offset + 2].copy_from_slice(&addr.to_be_bytes());
+            } else {
+                if addr > 0xFFF {
+                    return Err(self.error(
+                        &fixup.token,
+                        format!(
+                            "label '{}' at {:#06X} is out of 12 bit range",
+                            fixup.token.text, addr
+                        ),
+                    ));
+                }
+                self.rom[fixup.offset] |= (addr >> 8) as u8;
+                self.rom[fixup.offset + 1] = addr as u8;
+            }
+        }
+        Ok(self.rom)
+    }
+
+    fn statement(&mut self, token: &Token, cursor: &mut Cursor) -> Result<(), AsmError> {
+        let text = token.text.as_str();
+        if let Some(x) = register(text) {
+            return self.register_statement(token, x, cursor);
+        }
+        match text {
+            ":" => {
+                let name = cursor.expect(self, "a label name")?;
+                self.check_name(name)?;
+                if self
+                    .labels
+                    .insert(name.text.clone(), self.pos as u16)
+                    .is_some()
+                {
+                    return Err(
+                        self.error(name, format!("label '{}' is already defined", name.text))
+                    );
+                }
+            }
+            ":const" => {
+                let name = cursor.expect(self, "a constant name")?;
+                self.check_name(name)?;
+                let value = cursor.expect(self, "a value")?;
+                let value = self.number(value)?;
+                self.constants.insert(name.text.clone(), value);
+            }
+            ":byte" => {
+                let value = cursor.expect(self, "a byte")?;
+                let byte = self.byte(value)?;
+                self.emit(token, &[byte])?;
+            }
+            ":org" => {
+                let value = cursor.expect(self, "an address")?;
+                let addr = self.number(value)?;
+                if !(0..MEMORY_END as i64).contains(&addr) {
+                    return Err(self.error(value, "address out of range"));
+                }
+                let addr = addr as usize;
+                if !self.written {
+                    self.origin = addr;
+                } else if addr < self.origin {
+                    return Err(self.error(value, "cannot move before the start of the program"));
+                }
+                self.pos = addr;
+            }
+            ":call" => {
+                let target = self.target(cursor)?;
+                self.emit_target(token, |nnn| Instruction::CallSubroutine { nnn }, target)?;
+            }
+            "clear" => self.emit_op(token, Instruction::ClearScreen)?,
+            "return" => self.emit_op(token, Instruction::ReturnSubroutine)?,
+            "exit" => self.emit_op(token, Instruction::Exit)?,
+            "lores" => self.emit_op(token, Instruction::LowResolution)?,
+            "hires" => self.emit_op(token, Instruction::HighResolution)?,
+            "scroll-right" => self.emit_op(token, Instruction::ScrollRight)?,
+            "scroll-left" => self.emit_op(token, Instruction::ScrollLeft)?,
+            "audio" => self.emit_op(token, Instruction::LoadAudio)?,
+            "scroll-down" => {
+                let n = self.nibble(cursor)?;
+                self.emit_op(token, Instruction::ScrollDown { n })?;
+            }
+            "scroll-up" => {
+                let n = self.nibble(cursor)?;
+                self.emit_op(token, Instruction::ScrollUp { n })?;
+            }
+            "plane" => {
+                let n = self.nibble(cursor)?;
+                self.emit_op(token, Instruction::SelectPlanes { n })?;
+            }
+            "jump" => {
+                let target = self.target(cursor)?;
+                self.emit_target(token, |nnn| Instruction::Jump { nnn }, target)?;
+            }
+            "jump0" => {
+                let target = self.target(cursor)?;
+                self.emit_target(token, |nnn| Instruction::JumpWithOffset { nnn }, target)?;
+            }
+            "bcd" => {
+                let x = self.register(cursor)?;
+                self.emit_op(token, Instruction::BinaryCodedDecimalConversion { x })?;
+            }
+            "saveflags" => {
+                let x = self.register(cursor)?;
+                self.emit_op(token, Instruction::SaveFlags { x })?;
+            }
+            "loadflags" => {
+                let x = self.register(cursor)?;
+                self.emit_op(token, Instruction::LoadFlags { x })?;
+            }
+            "save" | "load" => {
+                let x = self.register(cursor)?;
+                // save vx - vy is the XO-CHIP range form
+                let instruction = if cursor.peek().is_some_and(|t| t.text == "-") {
+                    cursor.next();
+                    let y = self.register(cursor)?;
+                    if text == "save" {
+                        Instruction::SaveRange { x, y }
+                    } else {
+                        Instruction::LoadRange { x, y }
+                    }
+                } else if text == "save" {
+                    Instruction::StoreMemory { x }
+                } else {
+                    Instruction::LoadMemory { x }
+                };
+                self.emit_op(token, instruction)?;
+            }
+            "sprite" => {
+                let x = self.register(cursor)?;
+                let y = self.register(cursor)?;
+                let n = self.nibble(cursor)?;
+                self.emit_op(token, Instruction::Draw { x, y, n })?;
+            }
+            "if" => self.if_statement(token, cursor)?,
+            "i" => self.index_statement(token, cursor)?,
+            "delay" | "buzzer" | "pitch" => {
+                self.operator(cursor, ":=")?;
+                let x = self.register(cursor)?;
+                let instruction = match text {
+                    "delay" => Instruction::SetTimerToVX { x },
+                    "buzzer" => Instruction::SetSoundTimerToVX { x },
+                    _ => Instruction::SetPitch { x },
+                };
+                self.emit_op(token, instruction)?;
+            }
+            _ => {
+                if let Some(value) = self.literal(token) {
+                    let byte = self.to_byte(token, value)?;
+                    return self.emit(token, &[byte]);
+                }
+                if !is_name(text) {
+                    return Err(self.error(token, format!("unexpected '{}'", text)));
+                }
+                // A bare label name calls it
+                let target = Target::Label(token.clone());
+                self.emit_target(token, |nnn| Instruction::CallSubroutine { nnn }, target)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn register_statement(
+        &mut self,
+        token: &Token,
+        x: u8,
+        cursor: &mut Cursor,
+    ) -> Result<(), AsmError> {
+        let op = cursor.expect(self, "an operator")?;
+        let operand = cursor.expect(self, "an operand")?;
+        let vy = register(&operand.text);
+        let instruction = match (op.text.as_str(), vy) {
+            (":=", Some(y)) => Instruction::SetVXToVY { x, y },
+            (":=", None) => match operand.text.as_str() {
+                "random" => {
+                    let nn = self.byte_operand(cursor)?;
+                    Instruction::Random { x, nn }
+                }
+                "delay" => Instruction::SetVXToTimer { x },
+                "key" => Instruction::WaitKey { x },
+                _ => Instruction::SetVXToNN {
+                    x,
+                    nn: self.byte(operand)?,
+                },
+            },
+            ("+=", Some(y)) => Instruction::Add { x, y },
+            ("+=", None) => Instruction::AddNNToVX {
+                x,
+                nn: self.byte(operand)?,
+            },
+            // There is no subtract immediate, so add the two's complement
+            ("-=", None) => Instruction::AddNNToVX {
+                x,
+                nn: self.byte(operand)?.wrapping_neg(),
+            },
+            ("-=", Some(y)) => Instruction::SubtractFrom { x, y },
+            ("=-", Some(y)) => Instruction::Subtract { x, y },
+            ("|=", Some(y)) => Instruction::Or { x, y },
+            ("&=", Some(y)) => Instruction::And { x, y },
+            ("^=", Some(y)) => Instruction::Xor { x, y },
+            (">>=", Some(y)) => Instruction::RightShift { x, y },
+            ("<<=", Some(y)) => Instruction::LeftShift { x, y },
+            ("=-" | "|=" | "&=" | "^=" | ">>=" | "<<=", None) => {
+                return Err(self.error(operand, "expected a register"));
+            }
+            _ => return Err(self.error(op, format!("unknown operator '{}'", op.text))),
+        };
+        self.emit_op(token, instruction)
+    }
+
+    // The skips run the next instruction when their condition is false,
+    // so `if` assembles to the skip with the opposite condition
+    fn if_statement(&mut self, token: &Token, cursor: &mut Cursor) -> Result<(), AsmError> {
+        let x = self.register(cursor)?;
+        let op = cursor.expect(self, "a comparison")?;
+        let instruction = match op.text.as_str() {
+            "key" => Instruction::SkipKeyRelease { x },
+            "-key" => Instruction::SkipKeyPress { x },
+            "==" | "!=" => {
+                let operand = cursor.expect(self, "a register or a byte")?;
+                let equal = op.text == "==";
+                match (register(&operand.text), equal) {
+                    (Some(y), true) => Instruction::SkipVXNotEqualVY { x, y },
+                    (Some(y), false) => Instruction::SkipVXEqualVY { x, y },
+                    (None, true) => Instruction::SkipVXNotEqualNN {
+                        x,
+                        nn: self.byte(operand)?,
+                    },
+                    (None, false) => Instruction::SkipVXEqualNN {
+                        x,
+                        nn: self.byte(operand)?,
+                    },
+                }
+            }
+            _ => {
+                return Err(self.error(
+                    op,
+                    format!("expected ==, !=, key or -key, found '{}'", op.text),
+                ))
+            }
+        };
+        let then = cursor.expect(self, "then")?;
+        if then.text != "then" {
+            return Err(self.error(then, format!("expected then, found '{}'", then.text)));
+        }
+        self.emit_op(token, instruction)
+    }
+
+    fn index_statement(&mut self, token: &Token, cursor: &mut Cursor) -> Result<(), AsmError> {
+        let op = cursor.expect(self, "an operator")?;
+        match op.text.as_str() {
+            "+=" => {
+                let x = self.register(cursor)?;
+                self.emit_op(token, Instruction::AddToIndex { x })
+            }
+            ":=" => match cursor.peek().map(|t| t.text.as_str()) {
+                Some("hex") => {
+                    cursor.next();
+                    let x = self.register(cursor)?;
+                    self.emit_op(token, Instruction::FontCharacter { x })
+                }
+                Some("bighex") => {
+                    cursor.next();
+                    let x = self.register(cursor)?;
+                    self.emit_op(token, Instruction::BigFontCharacter { x })
+                }
+                Some("long") => {
+                    cursor.next();
+                    let target = self.target_wide(cursor)?;
+                    self.emit_op(token, Instruction::LongIndex)?;
+                    match target {
+                        Target::Address(addr) => self.emit(token, &addr.to_be_bytes()),
+                        Target::Label(label) => {
+                            self.fixups.push(Fixup {
+                                offset: self.offset(),
+                                wide: true,
+                                token: label,
+                            });
+                            self.emit(token, &[0, 0])
+                        }
+                    }
+                }
+                _ => {
+                    let target = self.target(cursor)?;
+                    self.emit_target(token, |nnn| Instruction::SetIndex { nnn }, target)
+                }
+            },
+            _ => Err(self.error(op, format!("expected := or +=, found '{}'", op.text))),
+        }
+    }
+
+    fn operator(&self, cursor: &mut Cursor, expected: &str) -> Result<(), AsmError> {
+        let op = cursor.expect(self, expected)?;
+        if op.text != expected {
+            return Err(self.error(op, format!("expected {}, found '{}'", expected, op.text)));
+        }
+        Ok(())
+    }
+
+    fn register(&self, cursor: &mut Cursor) -> Result<u8, AsmError> {
+        let token = cursor.expect(self, "a register")?;
+        register(&token.text).ok_or_else(|| {
+            self.error(
+                token,
+                format!("expected a register, found '{}'", token.text),
+            )
+        })
+    }
+
+    fn nibble(&self, cursor: &mut Cursor) -> Result<u8, AsmError> {
+        let token = cursor.expect(self, "a number")?;
+        let value = self.number(token)?;
+        if !(0..=0xF).contains(&value) {
+            return Err(self.error(token, format!("{} does not fit in 4 bits", value)));
+        }
+        Ok(value as u8)
+    }
+
+    fn byte_operand(&self, cursor: &mut Cursor) -> Result<u8, AsmError> {
+        let token = cursor.expect(self, "a byte")?;
+        self.byte(token)
+    }
+
+    fn byte(&self, token: &Token) -> Result<u8, AsmError> {
+        let value = self.number(token)?;
+        self.to_byte(token, value)
+    }
+
+    // Negative bytes are stored as two's complement
+    fn to_byte(&self, token: &Token, value: i64) -> Result<u8, AsmError> {
+        if !(-128..=255).contains(&value) {
+            return Err(self.error(token, format!("{} does not fit in a byte", value)));
+        }
+        Ok(value as u8)
+    }
+
+    // A numeric literal or a constant
+    fn number(&self, token: &Token) -> Result<i64, AsmError> {
+        self.literal(token)
+            .ok_or_else(|| self.error(token, format!("expected a number, found '{}'", token.text)))
+    }
+
+    fn literal(&self, token: &Token) -> Option<i64> {
+        parse_number(&token.text).or_else(|| self.constants.get(&token.text).copied())
+    }
+
+    fn target(&self, cursor: &mut Cursor) -> Result<Target, AsmError> {
+        self.address(cursor, 0xFFF)
+    }
+
+    fn target_wide(&self, cursor: &mut Cursor) -> Result<Target, AsmError> {
+        self.address(cursor, 0xFFFF)
+    }
+
+    fn address(&self, cursor: &mut Cursor, max: i64) -> Result<Target, AsmError> {
+        let token = cursor.expect(self, "an address")?;
+        if let Some(value) = self.literal(token) {
+            if !(0..=max).contains(&value) {
+                return Err(self.error(token, format!("address {:#X} is out of range", value)));
+            }
+            return Ok(Target::Address(value as u16));
+        }
+        if !is_name(&token.text) {
+            return Err(self.error(
+                token,
+                format!("expected an address, found '{}'", token.text),
+            ));
+        }
+        Ok(Target::Label(token.clone()))
+    }
+
+    fn check_name(&self, token: &Token) -> Result<(), AsmError> {
+        if !is_name(&token.text) {
+            return Err(self.error(token, format!("'{}' is not a valid name", token.text)));
+        }
+        Ok(())
+    }
+
+    fn offset(&self) -> usize {
+        self.pos - self.origin
+    }
+
+    // Emit an instruction with a 12 bit address, patched later if it is a label
+    fn emit_target(
+        &mut self,
+        token: &Token,
+        instruction: fn(u16) -> Instruction,
+        target: Target,
+    ) -> Result<(), AsmError> {
+        match target {
+            Target::Address(addr) => self.emit_op(token, instruction(addr)),
+            Target::Label(label) => {
+                self.fixups.push(Fixup {
+                    offset: self.offset(),
+                    wide: false,
+                    token: label,
+                });
+                self.emit_op(token, instruction(0))
+            }
+        }
+    }
+
+    fn emit_op(&mut self, token: &Token, instruction: Instruction) -> Result<(), AsmError> {
+        if !self.platform.supports(&instruction) {
+            let name = match instruction.extension() {
+                Some(Platform::SuperChip) => "SUPER-CHIP",
+                _ => "XO-CHIP",
+            };
+            return Err(self.error(token, format!("{} is a {} instruction", instruction, name)));
+        }
+        self.emit(token, &instruction.encode().to_be_bytes())
+    }
+
+    fn emit(&mut self, token: &Token, bytes: &[u8]) -> Result<(), AsmError> {
+        let end = self.pos + bytes.len();
+        if end > MEMORY_END {
+            return Err(self.error(token, "program does not fit in memory"));
+        }
+        let offset = self.offset();
+        if self.rom.len() < offset + bytes.len() {
+            self.rom.resize(offset + bytes.len(), 0);
+        }
+        self.rom[offset..offset + bytes.len()].copy_from_slice(bytes);
+        self.pos = end;
+        self.written = true;
+        Ok(())
+    }
+}
+
+struct Cursor<'a> {
+    tokens: &'a [Token],
+    idx: usize,
+}
+
+impl<'a> Cursor<'a> {
+    fn next(&mut self) -> Option<&'a Token> {
+        let token = self.tokens.get(self.idx)?;
+        self.idx += 1;
+        Some(token)
+    }
+
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.idx)
+    }
+
+    // The next token, or an error pointing at the last one when the input runs out
+    fn expect(&mut self, assembler: &Assembler, what: &str) -> Result<&'a Token, AsmError> {
+        match self.next() {
+            Some(token) => Ok(token),
+            None => {
+                let last = &self.tokens[self.tokens.len() - 1];
+                Err(assembler.error(last, format!("expected {} after '{}'", what, last.text)))
+            }
+        }
+    }
+}
+
+// v0 to vf
+fn register(text: &str) -> Option<u8> {
+    let digit = text.strip_prefix('v').or_else(|| text.strip_prefix('V'))?;
+    if digit.len() != 1 {
+        return None;
+    }
+    u8::from_str_radix(digit, 16).ok()
+}
+
+fn is_name(text: &str) -> bool {
+    let mut chars = text.chars();
+    let starts_well = chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_');
+    starts_well
+        && text
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && register(text).is_none()
+        && !KEYWORDS.contains(&text)
+}
+
+// Decimal, 0x hexadecimal or 0b binary, optionally negative
+fn parse_number(text: &str) -> Option<i64> {
+    let (negative, digits) = match text.strip_prefix('-') {
+        Some(rest) => (true, rest),
+        None => (false, text),
+    };
+    let value = if let Some(hex) = digits
+        .strip_prefix("0x")
+        .or_else(|| digits.strip_prefix("0X"))
+    {
+        i64::from_str_radix(hex, 16).ok()?
+    } else if let Some(bin) = digits
+        .strip_prefix("0b")
+        .or_else(|| digits.strip_prefix("0B"))
+    {
+        i64::from_str_radix(bin, 2).ok()?
+    } else if digits.chars().all(|c| c.is_ascii_digit()) && !digits.is_empty() {
+        digits.parse().ok()?
+    } else {
+        return None;
+    };
+    Some(if negative { -value } else { value })
+}
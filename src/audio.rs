@@ -0,0 +1,164 @@
+use crate::chip8::{AudioPattern, AUDIO_PATTERN_SIZE};
+use crate::clock::TIMER_HZ;
+use std::f32::consts::TAU;
+
+// Length of the fade in / fade out that keeps starts and stops from clicking
+const RAMP_SECONDS: f64 = 0.005;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Waveform {
+    #[default]
+    Square,
+    Triangle,
+    Sawtooth,
+    Sine,
+}
+
+impl Waveform {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "square" => Some(Self::Square),
+            "triangle" => Some(Self::Triangle),
+            "sawtooth" | "saw" => Some(Self::Sawtooth),
+            "sine" => Some(Self::Sine),
+            _ => None,
+        }
+    }
+
+    // Value of the wave at `phase`, which goes from 0 to 1 over one period
+    fn sample(self, phase: f32) -> f32 {
+        match self {
+            Waveform::Square => {
+                if phase < 0.5 {
+                    1.0
+                } else {
+                    -1.0
+                }
+            }
+            Waveform::Triangle => 1.0 - 4.0 * (phase - 0.5).abs(),
+            Waveform::Sawtooth => 2.0 * phase - 1.0,
+            Waveform::Sine => (phase * TAU).sin(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BuzzerSettings {
+    pub frequency: f32,
+    pub volume: f32,
+    pub waveform: Waveform,
+}
+
+impl Default for BuzzerSettings {
+    fn default() -> Self {
+        Self {
+            frequency: 440.0,
+            volume: 0.25,
+            waveform: Waveform::Square,
+        }
+    }
+}
+
+// Turns an XO-CHIP audio pattern into samples for the host sound card.
+// It does not depend on any audio API, the frontend feeds it output buffers.
+#[derive(Debug)]
+pub struct PatternPlayer {
+    output_rate: f64,
+    // Position in the pattern, in bits
+    position: f64,
+}
+
+impl PatternPlayer {
+    pub fn new(output_rate: i32) -> Self {
+        Self {
+            output_rate: output_rate as f64,
+            position: 0.0,
+        }
+    }
+
+    // Restart from the first bit, used when the sound timer starts again
+    pub fn reset(&mut self) {
+        self.position = 0.0;
+    }
+
+    // Returns the next sample, either 1 or -1
+    pub fn next_sample(&mut self, pattern: &AudioPattern) -> f32 {
+        // Each output sample advances the pattern by playback rate / output rate bits
+        let step = pattern.playback_rate() / self.output_rate;
+        let length = (AUDIO_PATTERN_SIZE * 8) as f64;
+        let sample = if pattern.bit(self.position as usize) {
+            1.0
+        } else {
+            -1.0
+        };
+        self.position = (self.position + step) % length;
+        sample
+    }
+}
+
+// The machine's sound output: a tone while the sound timer runs, or the
+// XO-CHIP pattern once a program has loaded one. The frontend reports the
+// sound timer every frame and the buzzer plays exactly that many 60ths of a
+// second, fading in and out over a few milliseconds so there are no clicks.
+#[derive(Debug)]
+pub struct Buzzer {
+    settings: BuzzerSettings,
+    output_rate: f64,
+    // Position in the current period of the tone, from 0 to 1
+    phase: f32,
+    pattern: Option<AudioPattern>,
+    pattern_player: PatternPlayer,
+    // Samples left before the sound timer reaches 0
+    remaining: usize,
+    gain: f32,
+    ramp_samples: f32,
+}
+
+impl Buzzer {
+    pub fn new(settings: BuzzerSettings, output_rate: i32) -> Self {
+        Self {
+            settings,
+            output_rate: output_rate as f64,
+            phase: 0.0,
+            pattern: None,
+            pattern_player: PatternPlayer::new(output_rate),
+            remaining: 0,
+            gain: 0.0,
+            ramp_samples: ((output_rate as f64 * RAMP_SECONDS) as f32).max(1.0),
+        }
+    }
+
+    // Called once per frame with the machine's sound timer and audio pattern
+    pub fn update(&mut self, sound_timer: u8, pattern: Option<AudioPattern>) {
+        if self.remaining == 0 && sound_timer > 0 {
+            // A new sound starts at the beginning of the wave
+            self.phase = 0.0;
+            self.pattern_player.reset();
+        }
+        self.remaining = (sound_timer as f64 * self.output_rate / TIMER_HZ as f64) as usize;
+        self.pattern = pattern;
+    }
+
+    pub fn fill(&mut self, out: &mut [f32]) {
+        let step = self.settings.frequency / self.output_rate as f32;
+        for sample in out.iter_mut() {
+            // Follow the remaining time so the fade out ends right as the timer runs
+            // out, but never move faster than the ramp allows
+            let target = (self.remaining as f32 / self.ramp_samples).min(1.0);
+            let max_change = 1.0 / self.ramp_samples;
+            self.gain += (target - self.gain).clamp(-max_change, max_change);
+            self.remaining = self.remaining.saturating_sub(1);
+
+            if self.gain == 0.0 {
+                *sample = 0.0;
+                continue;
+            }
+            let wave = match self.pattern {
+                Some(pattern) => self.pattern_player.next_sample(&pattern),
+                None => self.settings.waveform.sample(self.phase),
+            };
+            self.phase = (self.phase + step) % 1.0;
+            *sample = wave * self.gain * self.settings.volume;
+        }
+    }
+}
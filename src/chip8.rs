@@ -1,14 +1,37 @@
-use rand::random;
+use std::fmt;
+
+mod instruction;
+mod rng;
+mod savestate;
+mod trace;
+
+pub use instruction::{DecodeError, Instruction};
+pub use rng::Rng;
+pub use savestate::StateError;
+pub use trace::{FileTracer, StdoutTracer, TraceEvent, Tracer};
 
 pub const SCREEN_WIDTH: usize = 64;
 pub const SCREEN_HEIGHT: usize = 32;
+// SUPER-CHIP high resolution mode doubles the display in both directions
+pub const HIRES_SCREEN_WIDTH: usize = 128;
+pub const HIRES_SCREEN_HEIGHT: usize = 64;
 
-const START_ADDR: u16 = 0x200;
+// Programs are normally loaded right after the 512 bytes the original interpreter used
+pub const START_ADDR: u16 = 0x200;
+// The ETI-660 reserved more memory for its interpreter
+pub const ETI_660_START_ADDR: u16 = 0x600;
 const MEMORY_SIZE: usize = 4096;
+const XO_CHIP_MEMORY_SIZE: usize = 65536;
 const REGISTERS_COUNT: usize = 16;
 const STACK_SIZE: usize = 16;
 const KEYS_COUNT: usize = 16;
 const FONT_SIZE: usize = 80;
+const BIG_FONT_ADDR: usize = FONT_SIZE;
+const BIG_FONT_SIZE: usize = 160;
+const RPL_FLAGS_COUNT: usize = 16;
+pub const AUDIO_PATTERN_SIZE: usize = 16;
+// FX3A pitch value that plays the pattern back at 4000 bits per second
+const DEFAULT_PITCH: u8 = 64;
 
 const FONT: [u8; FONT_SIZE] = [
     0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
@@ -29,94 +52,254 @@ const FONT: [u8; FONT_SIZE] = [
     0xF0, 0x80, 0xF0, 0x80, 0x80, // F
 ];
 
-enum Instruction {
-    NOP,              // 0000
-    ClearScreen,      // 00E0
-    Jump,             // 1NNN
-    CallSubroutine,   // 2NNN
-    ReturnSubroutine, // 00EE
-    SkipVXEqualNN,    // 3XNN
-    SkipVXNotEqualNN, // 4XNN
-    SkipVXEqualVY,    // 5XY0
-    SkipVXNotEqualVY, // 9XY0
-    SetVXToNN,        // 6XNN
-    AddNNToVX,        // 7XNN
-    SetVXToVY,        // 8XY0
-    OR,               // 8XY1 (VX |= VY)
-    AND,              // 8XY2 (VX &= VY)
-    XOR,              // 8XY3 (VX ^= VY)
-    Add,              // 8XY4 (VX += VY)
-    SubtractFrom,     // 8XY5 (VX -= VY)
-    Subtract,         // 8XY7 (VX = VY - VX)
-    // TODO: Make RShift and LShift user configurable later
-    RightShift, // 8XY6 (VX >>= 1)
-    LeftShift,  // 8XYE ( VX <<= 1)
-    SetIndex,   // ANNN (I = NNN)
-    // TODO: Make Jump with offset user configurable later
-    JumpWithOffset,               // BNNN (JMP V0 + NNN)
-    Random,                       // CXNN (VX = rand & NN)
-    Display,                      // DXYN
-    SkipKeyPress,                 // EX9E
-    SkipKeyRelease,               // EXA1
-    SetVXToTimer,                 // FX07 VX = Delay Timer
-    SetTimerToVX,                 // FX15 Delay Timer = VX
-    SetSoundTimerToVX,            // FX18 Sound Timer = VX
-    AddToIndex,                   // FX1E (I += VX)
-    WaitKey,                      // FX0A
-    FontCharacter,                // FX29 (I = FONT)
-    BinaryCodedDecimalConversion, // FX33
-    StoreMemory,                  // FX55 (Store V0 to VX)
-    LoadMemory,                   // FX65 (Load V0 to VX)
+// SUPER-CHIP 10 byte tall digits used by FX30
+const BIG_FONT: [u8; BIG_FONT_SIZE] = [
+    0xFF, 0xFF, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, // 0
+    0x18, 0x78, 0x78, 0x18, 0x18, 0x18, 0x18, 0x18, 0xFF, 0xFF, // 1
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // 2
+    0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 3
+    0xC3, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0x03, 0x03, // 4
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 5
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 6
+    0xFF, 0xFF, 0x03, 0x03, 0x06, 0x0C, 0x18, 0x18, 0x18, 0x18, // 7
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, // 8
+    0xFF, 0xFF, 0xC3, 0xC3, 0xFF, 0xFF, 0x03, 0x03, 0xFF, 0xFF, // 9
+    0x7E, 0xFF, 0xC3, 0xC3, 0xC3, 0xFF, 0xFF, 0xC3, 0xC3, 0xC3, // A
+    0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, 0xC3, 0xC3, 0xFC, 0xFC, // B
+    0x3C, 0xFF, 0xC3, 0xC0, 0xC0, 0xC0, 0xC0, 0xC3, 0xFF, 0x3C, // C
+    0xFC, 0xFE, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xC3, 0xFE, 0xFC, // D
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, // E
+    0xFF, 0xFF, 0xC0, 0xC0, 0xFF, 0xFF, 0xC0, 0xC0, 0xC0, 0xC0, // F
+];
+
+// The machine a ROM was written for. The platform decides the memory size and
+// how instructions that only exist on the later interpreters behave.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Platform {
+    #[default]
+    Chip8,
+    SuperChip,
+    XoChip,
+}
+
+impl Platform {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "chip8" | "chip-8" => Some(Self::Chip8),
+            "schip" | "superchip" | "super-chip" => Some(Self::SuperChip),
+            "xochip" | "xo-chip" => Some(Self::XoChip),
+            _ => None,
+        }
+    }
+
+    // Whether the platform has `instruction`; XO-CHIP includes SUPER-CHIP's
+    pub fn supports(self, instruction: &Instruction) -> bool {
+        match instruction.extension() {
+            None => true,
+            Some(Platform::SuperChip) => self != Platform::Chip8,
+            Some(_) => self == Platform::XoChip,
+        }
+    }
+
+    pub fn default_quirks(self) -> Quirks {
+        match self {
+            Platform::Chip8 => Quirks::cosmac_vip(),
+            Platform::SuperChip => Quirks::superchip(),
+            Platform::XoChip => Quirks::xo_chip(),
+        }
+    }
+
+    fn memory_size(self) -> usize {
+        match self {
+            Platform::Chip8 | Platform::SuperChip => MEMORY_SIZE,
+            Platform::XoChip => XO_CHIP_MEMORY_SIZE,
+        }
+    }
+}
+
+// Behaviours that differ between the CHIP-8 interpreters that ROMs were written for.
+// See https://github.com/Timendus/chip8-test-suite#quirks-test for what each one does.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Quirks {
+    pub shift_uses_vy: bool, // 8XY6/8XYE copy VY into VX before shifting
+    pub jump_uses_vx: bool,  // BXNN jumps to XNN + VX instead of NNN + V0
+    pub memory_increments_index: bool, // FX55/FX65 leave I pointing past the last register
+    pub memory_increment_by_x: bool, // ...or at the last register, as CHIP-48 does
+    pub vf_reset: bool,      // 8XY1/8XY2/8XY3 reset VF to 0
+    pub clip_sprites: bool,  // DXYN clips sprites at the screen edge instead of wrapping
+    pub display_wait: bool,  // DXYN waits for the next 60 Hz frame before continuing
+}
+
+impl Quirks {
+    // The original interpreter on the RCA COSMAC VIP
+    pub fn cosmac_vip() -> Self {
+        Self {
+            shift_uses_vy: true,
+            jump_uses_vx: false,
+            memory_increments_index: true,
+            memory_increment_by_x: false,
+            vf_reset: true,
+            clip_sprites: true,
+            display_wait: true,
+        }
+    }
+
+    // CHIP-48 on the HP-48 calculators
+    pub fn chip48() -> Self {
+        Self {
+            shift_uses_vy: false,
+            jump_uses_vx: true,
+            memory_increments_index: true,
+            memory_increment_by_x: true,
+            vf_reset: false,
+            clip_sprites: true,
+            display_wait: false,
+        }
+    }
+
+    // SUPER-CHIP 1.1 as most modern SCHIP games expect it
+    pub fn superchip() -> Self {
+        Self {
+            shift_uses_vy: false,
+            jump_uses_vx: true,
+            memory_increments_index: false,
+            memory_increment_by_x: false,
+            vf_reset: false,
+            clip_sprites: true,
+            display_wait: false,
+        }
+    }
+
+    // XO-CHIP as implemented by Octo
+    pub fn xo_chip() -> Self {
+        Self {
+            shift_uses_vy: false,
+            jump_uses_vx: false,
+            memory_increments_index: true,
+            memory_increment_by_x: false,
+            vf_reset: false,
+            clip_sprites: false,
+            display_wait: false,
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "vip" | "cosmac" | "cosmac-vip" | "chip8" | "chip-8" => Some(Self::cosmac_vip()),
+            "chip48" | "chip-48" => Some(Self::chip48()),
+            "schip" | "superchip" | "super-chip" => Some(Self::superchip()),
+            "xochip" | "xo-chip" | "octo" => Some(Self::xo_chip()),
+            _ => None,
+        }
+    }
 }
 
-impl Instruction {
-    fn from_opcode(opcode: (u16, u16, u16, u16)) -> Option<Self> {
-        match opcode {
-            (0, 0, 0, 0) => Some(Self::NOP),                            // 0000
-            (0, 0, 0xE, 0) => Some(Self::ClearScreen),                  // 00E0
-            (1, _, _, _) => Some(Self::Jump),                           // 1NNN
-            (2, _, _, _) => Some(Self::CallSubroutine),                 // 2NNN
-            (0, 0, 0xE, 0xE) => Some(Self::ReturnSubroutine),           // 00EE
-            (3, _, _, _) => Some(Self::SkipVXEqualNN),                  // 3XNN
-            (4, _, _, _) => Some(Self::SkipVXNotEqualNN),               // 4XNN
-            (5, _, _, 0) => Some(Self::SkipVXEqualVY),                  // 5XY0
-            (9, _, _, 0) => Some(Self::SkipVXNotEqualVY),               // 9XY0
-            (6, _, _, _) => Some(Self::SetVXToNN),                      // 6XNN
-            (7, _, _, _) => Some(Self::AddNNToVX),                      // 7XNN
-            (8, _, _, 0) => Some(Self::SetVXToVY),                      // 8XY0
-            (8, _, _, 1) => Some(Self::OR),                             // 8XY1 (VX |= VY)
-            (8, _, _, 2) => Some(Self::AND),                            // 8XY2 (VX &= VY)
-            (8, _, _, 3) => Some(Self::XOR),                            // 8XY3 (VX ^= VY)
-            (8, _, _, 4) => Some(Self::Add),                            // 8XY4 (VX += VY)
-            (8, _, _, 5) => Some(Self::SubtractFrom),                   // 8XY5 (VX -= VY)
-            (8, _, _, 7) => Some(Self::Subtract),                       // 8XY7 (VX = VY - VX)
-            (8, _, _, 6) => Some(Self::RightShift),                     // 8XY6 (VX >>= 1)
-            (8, _, _, 0xE) => Some(Self::LeftShift),                    // 8XYE ( VX <<= 1)
-            (0xA, _, _, _) => Some(Self::SetIndex),                     // ANNN (I = NNN)
-            (0xB, _, _, _) => Some(Self::JumpWithOffset),               // BNNN (JMP V0 + NNN)
-            (0xC, _, _, _) => Some(Self::Random),                       // CXNN (VX = rand & NN)
-            (0xD, _, _, _) => Some(Self::Display),                      // DXYN
-            (0xE, _, _, 0xE) => Some(Self::SkipKeyPress),               // EX9E
-            (0xE, _, 0xA, 1) => Some(Self::SkipKeyRelease),             // EXA1
-            (0xF, _, 0, 7) => Some(Self::SetVXToTimer),                 // FX07 VX = Delay Timer
-            (0xF, _, 1, 5) => Some(Self::SetTimerToVX),                 // FX15 Delay Timer = VX
-            (0xF, _, 1, 8) => Some(Self::SetSoundTimerToVX),            // FX18 Sound Timer = VX
-            (0xF, _, 1, 0xE) => Some(Self::AddToIndex),                 // FX1E (I += VX)
-            (0xF, _, 0, 0xA) => Some(Self::WaitKey),                    // FX0A
-            (0xF, _, 2, 9) => Some(Self::FontCharacter),                // FX29 (I = FONT)
-            (0xF, _, 3, 3) => Some(Self::BinaryCodedDecimalConversion), // FX33
-            (0xF, _, 5, 5) => Some(Self::StoreMemory),                  // FX55 (Store V0 to VX)
-            (0xF, _, 6, 5) => Some(Self::LoadMemory),                   // FX65 (Load V0 to VX)
-            _ => return None,
+impl Default for Quirks {
+    fn default() -> Self {
+        Self::cosmac_vip()
+    }
+}
+
+// XO-CHIP sound: a loop of 128 one-bit samples played back at a rate set by the pitch
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AudioPattern {
+    pub buffer: [u8; AUDIO_PATTERN_SIZE],
+    pub pitch: u8,
+}
+
+impl AudioPattern {
+    // Number of pattern bits played per second, 4000 * 2 ^ ((pitch - 64) / 48)
+    pub fn playback_rate(&self) -> f64 {
+        4000.0 * 2f64.powf((self.pitch as f64 - DEFAULT_PITCH as f64) / 48.0)
+    }
+
+    // Returns bit `idx` of the pattern, most significant bit of the first byte first
+    pub fn bit(&self, idx: usize) -> bool {
+        let idx = idx % (AUDIO_PATTERN_SIZE * 8);
+        self.buffer[idx / 8] & (0b1000_0000 >> (idx % 8)) != 0
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Chip8Error {
+    StackOverflow,                              // 2NNN with all 16 stack entries in use
+    StackUnderflow,                             // 00EE with an empty stack
+    MemoryOutOfBounds { addr: usize, pc: u16 }, // Read or write past the end of memory
+    InvalidKey(u8),                             // EX9E / EXA1 with VX outside 0..=0xF
+    UnknownOpcode(u16),                         // Opcode that does not decode to an Instruction
+}
+
+impl fmt::Display for Chip8Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Chip8Error::StackOverflow => write!(f, "stack overflow"),
+            Chip8Error::StackUnderflow => write!(f, "stack underflow"),
+            Chip8Error::MemoryOutOfBounds { addr, pc } => write!(
+                f,
+                "memory access out of bounds at {:#05X} (pc = {:#05X})",
+                addr, pc
+            ),
+            Chip8Error::InvalidKey(key) => write!(f, "invalid key {:#04X}", key),
+            Chip8Error::UnknownOpcode(op) => write!(f, "unknown opcode {:#06X}", op),
+        }
+    }
+}
+
+impl std::error::Error for Chip8Error {}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadError {
+    Empty,                                // The ROM has no data
+    InvalidAddress(u16),                  // Load address inside the interpreter area or past memory
+    TooLarge { size: usize, max: usize }, // The ROM does not fit between the load address and the end of memory
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LoadError::Empty => write!(f, "the ROM is empty"),
+            LoadError::InvalidAddress(addr) => write!(
+                f,
+                "cannot load a ROM at {:#05X}, it must be between {:#05X} and the end of memory",
+                addr, START_ADDR
+            ),
+            LoadError::TooLarge { size, max } => write!(
+                f,
+                "the ROM is {} bytes but only {} bytes fit in memory",
+                size, max
+            ),
         }
     }
 }
 
+impl std::error::Error for LoadError {}
+
+// A load address given as decimal or 0x prefixed hexadecimal, such as 0x200
+pub fn parse_address(text: &str) -> Option<u16> {
+    match text.strip_prefix("0x").or_else(|| text.strip_prefix("0X")) {
+        Some(hex) => u16::from_str_radix(hex, 16).ok(),
+        None => text.parse().ok(),
+    }
+}
+
+impl From<DecodeError> for Chip8Error {
+    fn from(err: DecodeError) -> Self {
+        Chip8Error::UnknownOpcode(err.opcode)
+    }
+}
+
 #[derive(Debug)]
 pub struct Chip8 {
     pc: u16,
-    memory: [u8; MEMORY_SIZE],
-    screen: [bool; SCREEN_WIDTH * SCREEN_HEIGHT],
+    // 4 KiB, or 64 KiB on XO-CHIP
+    memory: Vec<u8>,
+    // Sized for the high resolution mode; only the first width * height
+    // pixels of the active resolution are in use. Each pixel holds one bit
+    // per drawing plane, plain CHIP-8 and SUPER-CHIP only use the first one.
+    screen: [u8; HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT],
+    hires: bool,
+    // XO-CHIP drawing planes affected by DXYN, 00E0 and scrolling
+    planes: u8,
     registers: [u8; REGISTERS_COUNT],
     index_register: u16,
     stack: [u16; STACK_SIZE],
@@ -124,20 +307,37 @@ pub struct Chip8 {
     keys: [bool; KEYS_COUNT],
     delay_timer: u8,
     sound_timer: u8,
+    platform: Platform,
+    quirks: Quirks,
+    // SUPER-CHIP persistent flags (HP-48 RPL user flags) for FX75/FX85
+    rpl_flags: [u8; RPL_FLAGS_COUNT],
+    // XO-CHIP audio pattern, None until a program loads one with F002
+    audio_pattern: Option<[u8; AUDIO_PATTERN_SIZE]>,
+    pitch: u8,
+    // Set by 00FD, the interpreter stops executing once a program exits
+    exited: bool,
+    // Set by DXYN when the display wait quirk is on, cleared by the next timer tick
+    waiting_for_vblank: bool,
+    // Source of CXNN's random numbers
+    rng: Rng,
+    tracer: Option<Box<dyn Tracer>>,
 }
 
 impl Chip8 {
-    pub fn new() -> Self {
-        let mut memory = [0; MEMORY_SIZE];
+    pub fn new(platform: Platform, quirks: Quirks) -> Self {
+        let mut memory = vec![0; platform.memory_size()];
 
         // The font is loaded and available
         // at the start of the memory
         memory[..FONT_SIZE].copy_from_slice(&FONT);
+        memory[BIG_FONT_ADDR..BIG_FONT_ADDR + BIG_FONT_SIZE].copy_from_slice(&BIG_FONT);
 
-        let chip8 = Self {
+        Self {
             pc: START_ADDR,
             memory,
-            screen: [false; SCREEN_WIDTH * SCREEN_HEIGHT],
+            screen: [0; HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT],
+            hires: false,
+            planes: 1,
             registers: [0; REGISTERS_COUNT],
             index_register: 0,
             stack: [0; STACK_SIZE],
@@ -145,53 +345,207 @@ impl Chip8 {
             keys: [false; KEYS_COUNT],
             delay_timer: 0,
             sound_timer: 0,
+            platform,
+            quirks,
+            rpl_flags: [0; RPL_FLAGS_COUNT],
+            audio_pattern: None,
+            pitch: DEFAULT_PITCH,
+            exited: false,
+            waiting_for_vblank: false,
+            rng: Rng::from_time(),
+            tracer: None,
+        }
+    }
+
+    pub fn tick(&mut self) -> Result<(), Chip8Error> {
+        if self.waiting_for_vblank || self.exited {
+            return Ok(());
+        }
+        // fetch, decode and execute loop the heart of the emulator
+        let pc = self.pc;
+        let op = self.fetch()?;
+        if self.tracer.is_none() {
+            return self.decode_and_execute(op).map(|_| ());
+        }
+
+        let registers_before = self.registers;
+        let index_before = self.index_register;
+        let instruction = self.decode_and_execute(op)?;
+        let event = TraceEvent {
+            pc,
+            opcode: op,
+            instruction,
+            registers_before,
+            registers_after: self.registers,
+            index_before,
+            index_after: self.index_register,
         };
+        if let Some(tracer) = self.tracer.as_mut() {
+            tracer.trace(&event);
+        }
+        Ok(())
+    }
 
-        chip8
+    // Makes CXNN produce the same numbers on every run started with the same seed
+    pub fn set_seed(&mut self, seed: u64) {
+        self.rng = Rng::new(seed);
     }
 
-    pub fn tick(&mut self) {
-        // fetch, decode and execute loop the heart of the emulator
-        let op = self.fetch();
-        self.decode_and_execute(op);
+    // Every instruction executed from now on is reported to `tracer`
+    pub fn set_tracer(&mut self, tracer: Box<dyn Tracer>) {
+        self.tracer = Some(tracer);
     }
 
     pub fn tick_timers(&mut self) {
         // Timers should be decremented by one 60 times per second (ie. at 60 Hz) as long
         // as their value is above 0
+        self.waiting_for_vblank = false;
         if self.delay_timer > 0 {
             self.delay_timer -= 1;
         }
+        // The buzzer sounds for as long as the sound timer is above 0,
+        // frontends poll sound_timer to drive their audio output
         if self.sound_timer > 0 {
-            if self.sound_timer == 1 {
-                // TODO: use some sound api to make a beep
-            }
             self.sound_timer -= 1;
         }
     }
 
-    pub fn get_screen(&self) -> &[bool] {
-        return &self.screen;
+    // Returns the pixels of the active resolution along with its width and height.
+    // Each pixel is a bit mask of the planes it is lit on, so 0 is always background.
+    pub fn get_screen(&self) -> (&[u8], usize, usize) {
+        let (width, height) = self.resolution();
+        (&self.screen[..width * height], width, height)
+    }
+
+    // Number of 60 Hz ticks the buzzer has left to sound,
+    // the buzzer is active for as long as this is above 0
+    pub fn sound_timer(&self) -> u8 {
+        self.sound_timer
+    }
+
+    // The XO-CHIP pattern to play while the sound timer runs, if the program loaded one
+    pub fn audio_pattern(&self) -> Option<AudioPattern> {
+        self.audio_pattern.map(|buffer| AudioPattern {
+            buffer,
+            pitch: self.pitch,
+        })
+    }
+
+    pub fn has_exited(&self) -> bool {
+        self.exited
+    }
+
+    pub fn pc(&self) -> u16 {
+        self.pc
+    }
+
+    pub fn index_register(&self) -> u16 {
+        self.index_register
+    }
+
+    pub fn registers(&self) -> &[u8] {
+        &self.registers
+    }
+
+    pub fn delay_timer(&self) -> u8 {
+        self.delay_timer
+    }
+
+    // The return addresses currently on the stack, oldest first
+    pub fn stack(&self) -> &[u16] {
+        &self.stack[..self.sp as usize]
+    }
+
+    pub fn memory(&self) -> &[u8] {
+        &self.memory
+    }
+
+    // True while DXYN waits for the next timer tick, during which tick() does nothing
+    pub fn is_waiting_for_vblank(&self) -> bool {
+        self.waiting_for_vblank
+    }
+
+    // The setters below let debuggers change the machine between instructions
+
+    pub fn set_pc(&mut self, pc: u16) {
+        self.pc = pc;
+    }
+
+    pub fn set_index_register(&mut self, value: u16) {
+        self.index_register = value;
+    }
+
+    pub fn set_register(&mut self, x: usize, value: u8) {
+        self.registers[x] = value;
+    }
+
+    pub fn set_delay_timer(&mut self, value: u8) {
+        self.delay_timer = value;
+    }
+
+    pub fn set_sound_timer(&mut self, value: u8) {
+        self.sound_timer = value;
+    }
+
+    pub fn write_memory(&mut self, addr: u16, data: &[u8]) -> Result<(), Chip8Error> {
+        let start = addr as usize;
+        let end = start + data.len();
+        if end > self.memory.len() {
+            return Err(Chip8Error::MemoryOutOfBounds {
+                addr: start.max(self.memory.len()),
+                pc: self.pc,
+            });
+        }
+        self.memory[start..end].copy_from_slice(data);
+        Ok(())
     }
 
     pub fn keypress(&mut self, idx: usize, pressed: bool) {
         self.keys[idx] = pressed;
     }
 
-    pub fn load(&mut self, data: &[u8]) {
-        // Load the whole ROM in memory starting from the
-        // START_ADDR which will be 0x200
-        let start = START_ADDR as usize;
-        let end = (START_ADDR as usize) + data.len();
+    pub fn load(&mut self, data: &[u8], addr: u16) -> Result<(), LoadError> {
+        // Load the whole ROM in memory starting from `addr`, normally
+        // START_ADDR which will be 0x200, and start executing from there
+        if addr < START_ADDR || addr as usize >= self.memory.len() {
+            return Err(LoadError::InvalidAddress(addr));
+        }
+        if data.is_empty() {
+            return Err(LoadError::Empty);
+        }
+        let max = self.max_rom_size(addr);
+        if data.len() > max {
+            return Err(LoadError::TooLarge {
+                size: data.len(),
+                max,
+            });
+        }
+
+        let start = addr as usize;
+        let end = start + data.len();
         self.memory[start..end].copy_from_slice(data);
+        self.pc = addr;
+        Ok(())
+    }
+
+    // Largest ROM that fits in memory when loaded at `addr`
+    pub fn max_rom_size(&self, addr: u16) -> usize {
+        self.memory.len().saturating_sub(addr as usize)
     }
 
-    fn fetch(&mut self) -> u16 {
+    fn fetch(&mut self) -> Result<u16, Chip8Error> {
         // Read the instruction that PC is currently pointing at from memory.
         // An instruction is two bytes, so we need to read two successive bytes from memory
         // and combine them into one 16-bit instruction.
-        let first_byte = self.memory[self.pc as usize] as u16;
-        let second_byte = self.memory[(self.pc + 1) as usize] as u16;
+        let addr = self.pc as usize;
+        if addr + 1 >= self.memory.len() {
+            return Err(Chip8Error::MemoryOutOfBounds {
+                addr: addr + 1,
+                pc: self.pc,
+            });
+        }
+        let first_byte = self.memory[addr] as u16;
+        let second_byte = self.memory[addr + 1] as u16;
         // We want to combine the two instructions into one 16 bit instruction
         // To do that: left shift by 8 on first byte and then logical OR the second byte
         // Example: There are two bytes 00000100 (4) and 00000101 (5)
@@ -201,356 +555,575 @@ impl Chip8 {
         let opcode = (first_byte << 8) | second_byte;
 
         // Increment Program Counter by 2 as we fetched 2 bytes to form an opcode above
-        self.pc += 2;
-        opcode
+        self.pc = self.pc.wrapping_add(2);
+        Ok(opcode)
     }
 
-    fn push_on_stack(&mut self, val: u16) {
+    fn skip_next(&mut self) {
+        // Skip over the next instruction. On XO-CHIP that may be the
+        // four byte F000 NNNN, so decode it the way it would be run to
+        // know how far to go.
+        let addr = self.pc as usize;
+        let mut len = 2;
+        if addr + 1 < self.memory.len() {
+            let next = u16::from_be_bytes([self.memory[addr], self.memory[addr + 1]]);
+            if let Ok(instruction) = self.decode(next) {
+                len = instruction.size();
+            }
+        }
+        self.pc = self.pc.wrapping_add(len);
+    }
+
+    fn push_on_stack(&mut self, val: u16) -> Result<(), Chip8Error> {
+        if self.sp as usize >= STACK_SIZE {
+            return Err(Chip8Error::StackOverflow);
+        }
         self.stack[self.sp as usize] = val;
         self.sp += 1;
+        Ok(())
     }
 
-    fn pop_from_stack(&mut self) -> u16 {
+    fn pop_from_stack(&mut self) -> Result<u16, Chip8Error> {
+        if self.sp == 0 {
+            return Err(Chip8Error::StackUnderflow);
+        }
         self.sp -= 1;
-        self.stack[self.sp as usize]
-    }
-
-    fn decode_and_execute(&mut self, op: u16) {
-        /*
-            1010 0010 0010 1010 -> 41514 -> OP Example
-            First Nibble (half-byte) -> 1010 to Hexadecimal
-            Second Nibble ...
-        */
-        let nibble_1 = (op >> 12) & 0b1111;
-        let nibble_2 = (op >> 8) & 0b1111;
-        let nibble_3 = (op >> 4) & 0b1111;
-        let nibble_4 = op & 0b1111;
-
-        println!("Executing Opcode: {}", op);
-
-        let opcode = (nibble_1, nibble_2, nibble_3, nibble_4);
-
-        if let Some(instruction) = Instruction::from_opcode(opcode) {
-            match instruction {
-                Instruction::NOP => return,
-                Instruction::ClearScreen => {
-                    println!("Executing Clear Screen: {}", op);
-                    // Turn all pixels off; set all values in screen to false
-                    self.screen = [false; SCREEN_WIDTH * SCREEN_HEIGHT];
-                }
-                Instruction::Jump => {
-                    println!("Executing JUMP: {}", op);
-                    // This instruction should simply set PC to NNN
-                    // causing the program to jump to that memory location.
-                    self.pc = op & 0xFFF;
-                }
-                Instruction::SetVXToNN => {
-                    // 6XNN
-                    println!("Executing SetVXToNN: {}", op);
-                    let nn = (op & 0xFF) as u8;
-                    self.registers[nibble_2 as usize] = nn;
-                }
-                Instruction::AddNNToVX => {
-                    // 7XNN
-                    println!("Executing AddNNToVX: {}", op);
-                    let nn = (op & 0xFF) as u8;
-                    self.registers[nibble_2 as usize] =
-                        self.registers[nibble_2 as usize].wrapping_add(nn);
-                }
-                Instruction::SetIndex => {
-                    // ANNN (I = NNN)
-                    println!("Executing SetIndex: {}", op);
-                    self.index_register = op & 0xFFF;
+        Ok(self.stack[self.sp as usize])
+    }
+
+    fn resolution(&self) -> (usize, usize) {
+        if self.hires {
+            (HIRES_SCREEN_WIDTH, HIRES_SCREEN_HEIGHT)
+        } else {
+            (SCREEN_WIDTH, SCREEN_HEIGHT)
+        }
+    }
+
+    fn set_resolution(&mut self, hires: bool) {
+        // Switching modes clears the display as the pixel layout changes
+        self.hires = hires;
+        self.screen = [0; HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT];
+    }
+
+    fn scroll(&mut self, dx: isize, dy: isize) {
+        // Move every pixel of the selected planes by (dx, dy);
+        // pixels scrolled in from the edge are off
+        let (width, height) = self.resolution();
+        let old = self.screen;
+        for y in 0..height {
+            for x in 0..width {
+                let src_x = x as isize - dx;
+                let src_y = y as isize - dy;
+                let inside =
+                    (0..width as isize).contains(&src_x) && (0..height as isize).contains(&src_y);
+                let moved = if inside {
+                    old[src_x as usize + width * src_y as usize]
+                } else {
+                    0
+                };
+                let idx = x + width * y;
+                self.screen[idx] = (old[idx] & !self.planes) | (moved & self.planes);
+            }
+        }
+    }
+
+    // Where FX55/FX65 leave I after touching registers 0 to `x`
+    fn advance_index(&mut self, x: usize) {
+        if self.quirks.memory_increments_index {
+            let step = if self.quirks.memory_increment_by_x {
+                x
+            } else {
+                x + 1
+            };
+            self.index_register = self.index_register.wrapping_add(step as u16);
+        }
+    }
+
+    fn check_memory(&self, addr: usize) -> Result<usize, Chip8Error> {
+        // Memory accesses only happen in instructions that leave PC alone,
+        // so the faulting instruction is the one that was just fetched.
+        if addr < self.memory.len() {
+            Ok(addr)
+        } else {
+            Err(Chip8Error::MemoryOutOfBounds {
+                addr,
+                pc: self.pc.wrapping_sub(2),
+            })
+        }
+    }
+
+    fn key_pressed(&self, key: u8) -> Result<bool, Chip8Error> {
+        match self.keys.get(key as usize) {
+            Some(pressed) => Ok(*pressed),
+            None => Err(Chip8Error::InvalidKey(key)),
+        }
+    }
+
+    // Opcodes from an extension the platform lacks are as unknown as any other
+    fn decode(&self, op: u16) -> Result<Instruction, Chip8Error> {
+        match Instruction::decode(op)? {
+            instruction if self.platform.supports(&instruction) => Ok(instruction),
+            _ => Err(Chip8Error::UnknownOpcode(op)),
+        }
+    }
+
+    fn decode_and_execute(&mut self, op: u16) -> Result<Instruction, Chip8Error> {
+        let instruction = self.decode(op)?;
+
+        match instruction {
+            Instruction::Nop => {}
+            Instruction::ClearScreen => {
+                // Turn all pixels off on the selected planes
+                for pixel in self.screen.iter_mut() {
+                    *pixel &= !self.planes;
                 }
-                Instruction::Display => {
-                    println!("Executing Display: {}", op);
-                    // https://tobiasvl.github.io/blog/write-a-chip-8-emulator/#dxyn-display
-                    // Get the x,y coordinates from the registers
-                    let x_coord = self.registers[nibble_2 as usize] as u16;
-                    let y_coord = self.registers[nibble_3 as usize] as u16;
-                    // The last digit determines how many rows high the sprite is
-                    let num_rows = nibble_4;
-
-                    // Keep track if any pixels were flipped
-                    let mut flipped = false;
+            }
+            Instruction::Jump { nnn } => {
+                // This instruction should simply set PC to NNN
+                // causing the program to jump to that memory location.
+                self.pc = nnn;
+            }
+            Instruction::SetVXToNN { x, nn } => {
+                // 6XNN
+                self.registers[x as usize] = nn;
+            }
+            Instruction::AddNNToVX { x, nn } => {
+                // 7XNN
+                let x = x as usize;
+                self.registers[x] = self.registers[x].wrapping_add(nn);
+            }
+            Instruction::SetIndex { nnn } => {
+                // ANNN (I = NNN)
+                self.index_register = nnn;
+            }
+            Instruction::Draw { x, y, n } => {
+                // https://tobiasvl.github.io/blog/write-a-chip-8-emulator/#dxyn-display
+                let (width, height) = self.resolution();
+                // Get the x,y coordinates from the registers. The starting position
+                // always wraps, only the pixels drawn past the edge may be clipped.
+                let x_coord = self.registers[x as usize] as usize % width;
+                let y_coord = self.registers[y as usize] as usize % height;
+                // The last digit determines how many rows high the sprite is.
+                // SUPER-CHIP uses 0 for a 16x16 sprite made of two bytes per row,
+                // on plain CHIP-8 it draws nothing.
+                let (num_rows, num_cols) = match n {
+                    0 if self.platform != Platform::Chip8 => (16, 16),
+                    _ => (n as usize, 8),
+                };
+                let bytes_per_row = num_cols / 8;
+
+                // Keep track if any pixels were flipped
+                let mut flipped = false;
+                // XO-CHIP draws the sprite once per selected plane, reading the
+                // data for each plane right after the previous one
+                let mut sprite_addr = self.index_register as usize;
+                for plane in [1u8, 2] {
+                    if self.planes & plane == 0 {
+                        continue;
+                    }
                     // Iterate over each row of the sprite
                     for y_line in 0..num_rows {
                         // Determine which memory address the rows data is stored
-                        let addr = self.index_register + y_line;
-                        let pixels = self.memory[addr as usize];
+                        let addr = sprite_addr + y_line * bytes_per_row;
+                        self.check_memory(addr + bytes_per_row - 1)?;
+                        let mut pixels = 0u16;
+                        for byte in &self.memory[addr..addr + bytes_per_row] {
+                            pixels = (pixels << 8) | *byte as u16;
+                        }
                         // Iterate over each column in the row
-                        for x_line in 0..8 {
+                        for x_line in 0..num_cols {
                             // Use a mask to fetch current pixels bit. Only flip if a 1
-                            if (pixels & (0b1000_0000 >> x_line)) != 0 {
-                                // Sprites should wrap around screen, so apply modulo
-                                let x = (x_coord + x_line) as usize % SCREEN_WIDTH;
-                                let y = (y_coord + y_line) as usize % SCREEN_HEIGHT;
+                            if (pixels & (1 << (num_cols - 1 - x_line))) != 0 {
+                                let x = x_coord + x_line;
+                                let y = y_coord + y_line;
+                                if self.quirks.clip_sprites && (x >= width || y >= height) {
+                                    continue;
+                                }
+                                // Otherwise sprites wrap around screen, so apply modulo
+                                let x = x % width;
+                                let y = y % height;
 
                                 // Get the pixel's index in the 1D screen array
-                                let idx = x + SCREEN_WIDTH * y;
+                                let idx = x + width * y;
                                 // Check if about to flip the pixel and set
-                                flipped |= self.screen[idx];
-                                self.screen[idx] ^= true;
+                                flipped |= self.screen[idx] & plane != 0;
+                                self.screen[idx] ^= plane;
                             }
                         }
                     }
-                    // Populate VF register
-                    if flipped {
-                        self.registers[0xF] = 1;
-                    } else {
-                        self.registers[0xF] = 0;
-                    }
+                    sprite_addr += num_rows * bytes_per_row;
                 }
-                Instruction::CallSubroutine => {
-                    // Calls the subroutine at memory location NNN i.e should set PC to NNN.
-                    let nnn = op & 0xFFF;
-                    self.push_on_stack(self.pc);
-                    self.pc = nnn;
+                // Populate VF register
+                if flipped {
+                    self.registers[0xF] = 1;
+                } else {
+                    self.registers[0xF] = 0;
                 }
-                Instruction::ReturnSubroutine => {
-                    // Return from a subroutine by popping the last address
-                    // from the stack and setting the PC to it
-                    let addr = self.pop_from_stack();
-                    self.pc = addr;
+                if self.quirks.display_wait {
+                    self.waiting_for_vblank = true;
                 }
-                Instruction::SkipVXEqualNN => {
-                    // 3XNN
-                    let x = nibble_2 as usize;
-                    let nn = (op & 0xFF) as u8;
-                    if self.registers[x] == nn {
-                        self.pc += 2;
-                    }
-                }
-                Instruction::SkipVXNotEqualNN => {
-                    // 4XNN
-                    let x = nibble_2 as usize;
-                    let nn = (op & 0xFF) as u8;
-                    if self.registers[x] != nn {
-                        self.pc += 2;
-                    }
-                }
-                Instruction::SkipVXEqualVY => {
-                    // 5XY0
-                    let x = nibble_2 as usize;
-                    let y = nibble_3 as usize;
-                    if self.registers[x] == self.registers[y] {
-                        self.pc += 2;
-                    }
-                }
-                Instruction::SkipVXNotEqualVY => {
-                    // 9XY0
-                    let x = nibble_2 as usize;
-                    let y = nibble_3 as usize;
-                    if self.registers[x] != self.registers[y] {
-                        self.pc += 2;
-                    }
-                }
-                Instruction::SetVXToVY => {
-                    // 8XY0
-                    let x = nibble_2 as usize;
-                    let y = nibble_3 as usize;
-                    self.registers[x] = self.registers[y];
-                }
-                Instruction::OR => {
-                    // 8XY1 (VX |= VY)
-                    let x = nibble_2 as usize;
-                    let y = nibble_3 as usize;
-                    self.registers[x] |= self.registers[y];
+            }
+            Instruction::CallSubroutine { nnn } => {
+                // Calls the subroutine at memory location NNN i.e should set PC to NNN.
+                self.push_on_stack(self.pc)?;
+                self.pc = nnn;
+            }
+            Instruction::ReturnSubroutine => {
+                // Return from a subroutine by popping the last address
+                // from the stack and setting the PC to it
+                let addr = self.pop_from_stack()?;
+                self.pc = addr;
+            }
+            Instruction::SkipVXEqualNN { x, nn } => {
+                // 3XNN
+                let x = x as usize;
+                if self.registers[x] == nn {
+                    self.skip_next();
                 }
-                Instruction::AND => {
-                    // 8XY2 (VX &= VY)
-                    let x = nibble_2 as usize;
-                    let y = nibble_3 as usize;
-                    self.registers[x] &= self.registers[y];
+            }
+            Instruction::SkipVXNotEqualNN { x, nn } => {
+                // 4XNN
+                let x = x as usize;
+                if self.registers[x] != nn {
+                    self.skip_next();
                 }
-                Instruction::XOR => {
-                    // 8XY3 (VX ^= VY)
-                    let x = nibble_2 as usize;
-                    let y = nibble_3 as usize;
-                    self.registers[x] ^= self.registers[y];
+            }
+            Instruction::SkipVXEqualVY { x, y } => {
+                // 5XY0
+                let x = x as usize;
+                let y = y as usize;
+                if self.registers[x] == self.registers[y] {
+                    self.skip_next();
                 }
-                Instruction::Add => {
-                    // 8XY4 (VX += VY)
-                    // If the result is larger than 255 (and thus overflows the 8-bit register
-                    // VX), the flag register VF is set to 1. If it doesn't overflow,
-                    // VF is set to 0.
-                    let x = nibble_2 as usize;
-                    let y = nibble_3 as usize;
-
-                    let (new_vx, carry) = self.registers[x].overflowing_add(self.registers[y]);
-                    let new_vf = if carry { 1 } else { 0 };
-
-                    self.registers[x] = new_vx;
-                    self.registers[0xF] = new_vf;
+            }
+            Instruction::SkipVXNotEqualVY { x, y } => {
+                // 9XY0
+                let x = x as usize;
+                let y = y as usize;
+                if self.registers[x] != self.registers[y] {
+                    self.skip_next();
                 }
-                Instruction::SubtractFrom => {
-                    // 8XY5 (VX -= VY)
-                    // If the minuend (the first operand) is larger than the subtrahend
-                    // (second operand), VF will be set to 1. If the subtrahend is larger, and
-                    // “underflow” the result, VF is set to 0.
-                    let x = nibble_2 as usize;
-                    let y = nibble_3 as usize;
-
-                    let (new_vx, borrow) = self.registers[x].overflowing_sub(self.registers[y]);
-                    let new_vf = if borrow { 0 } else { 1 };
-
-                    self.registers[x] = new_vx;
-                    self.registers[0xF] = new_vf;
+            }
+            Instruction::SetVXToVY { x, y } => {
+                // 8XY0
+                let x = x as usize;
+                let y = y as usize;
+                self.registers[x] = self.registers[y];
+            }
+            Instruction::Or { x, y } => {
+                // 8XY1 (VX |= VY)
+                let x = x as usize;
+                let y = y as usize;
+                self.registers[x] |= self.registers[y];
+                if self.quirks.vf_reset {
+                    self.registers[0xF] = 0;
                 }
-                Instruction::Subtract => {
-                    // 8XY7 (VX = VY - VX)
-                    // If the minuend (the first operand) is larger than the subtrahend
-                    // (second operand), VF will be set to 1. If the subtrahend is larger, and
-                    // “underflow” the result, VF is set to 0.
-                    let x = nibble_2 as usize;
-                    let y = nibble_3 as usize;
-
-                    let (new_vx, borrow) = self.registers[y].overflowing_sub(self.registers[x]);
-                    let new_vf = if borrow { 0 } else { 1 };
-
-                    self.registers[x] = new_vx;
-                    self.registers[0xF] = new_vf;
+            }
+            Instruction::And { x, y } => {
+                // 8XY2 (VX &= VY)
+                let x = x as usize;
+                let y = y as usize;
+                self.registers[x] &= self.registers[y];
+                if self.quirks.vf_reset {
+                    self.registers[0xF] = 0;
                 }
-                Instruction::RightShift => {
-                    // 8XY6 (VX >>= 1)
-                    let x = nibble_2 as usize;
-                    let lsb = self.registers[x] & 1;
-                    self.registers[x] >>= 1;
-                    self.registers[0xF] = lsb;
+            }
+            Instruction::Xor { x, y } => {
+                // 8XY3 (VX ^= VY)
+                let x = x as usize;
+                let y = y as usize;
+                self.registers[x] ^= self.registers[y];
+                if self.quirks.vf_reset {
+                    self.registers[0xF] = 0;
                 }
-                Instruction::LeftShift => {
-                    // 8XYE ( VX <<= 1)
-                    let x = nibble_2 as usize;
-                    let msb = (self.registers[x] >> 7) & 1;
-                    self.registers[x] <<= 1;
-                    self.registers[0xF] = msb;
+            }
+            Instruction::Add { x, y } => {
+                // 8XY4 (VX += VY)
+                // If the result is larger than 255 (and thus overflows the 8-bit register
+                // VX), the flag register VF is set to 1. If it doesn't overflow,
+                // VF is set to 0.
+                let x = x as usize;
+                let y = y as usize;
+
+                let (new_vx, carry) = self.registers[x].overflowing_add(self.registers[y]);
+                let new_vf = if carry { 1 } else { 0 };
+
+                self.registers[x] = new_vx;
+                self.registers[0xF] = new_vf;
+            }
+            Instruction::SubtractFrom { x, y } => {
+                // 8XY5 (VX -= VY)
+                // If the minuend (the first operand) is larger than the subtrahend
+                // (second operand), VF will be set to 1. If the subtrahend is larger, and
+                // “underflow” the result, VF is set to 0.
+                let x = x as usize;
+                let y = y as usize;
+
+                let (new_vx, borrow) = self.registers[x].overflowing_sub(self.registers[y]);
+                let new_vf = if borrow { 0 } else { 1 };
+
+                self.registers[x] = new_vx;
+                self.registers[0xF] = new_vf;
+            }
+            Instruction::Subtract { x, y } => {
+                // 8XY7 (VX = VY - VX)
+                // If the minuend (the first operand) is larger than the subtrahend
+                // (second operand), VF will be set to 1. If the subtrahend is larger, and
+                // “underflow” the result, VF is set to 0.
+                let x = x as usize;
+                let y = y as usize;
+
+                let (new_vx, borrow) = self.registers[y].overflowing_sub(self.registers[x]);
+                let new_vf = if borrow { 0 } else { 1 };
+
+                self.registers[x] = new_vx;
+                self.registers[0xF] = new_vf;
+            }
+            Instruction::RightShift { x, y } => {
+                // 8XY6 (VX >>= 1)
+                let x = x as usize;
+                if self.quirks.shift_uses_vy {
+                    self.registers[x] = self.registers[y as usize];
                 }
-                Instruction::JumpWithOffset => {
-                    // BNNN (JMP V0 + NNN)
-                    let nnn = op & 0xFFF;
-                    self.pc = (self.registers[0] as u16) + nnn;
+                let lsb = self.registers[x] & 1;
+                self.registers[x] >>= 1;
+                self.registers[0xF] = lsb;
+            }
+            Instruction::LeftShift { x, y } => {
+                // 8XYE ( VX <<= 1)
+                let x = x as usize;
+                if self.quirks.shift_uses_vy {
+                    self.registers[x] = self.registers[y as usize];
                 }
-                Instruction::Random => {
-                    // CXNN (VX = rand & NN)
-                    // generates a random number, binary ANDs it with the value NN, and
-                    // puts the result in VX
-                    let x = nibble_2 as usize;
-                    let nn = (op & 0xFF) as u8;
-                    let rng: u8 = random();
-                    self.registers[x] = rng & nn;
+                let msb = (self.registers[x] >> 7) & 1;
+                self.registers[x] <<= 1;
+                self.registers[0xF] = msb;
+            }
+            Instruction::JumpWithOffset { nnn } => {
+                // BNNN (JMP V0 + NNN)
+                // With the jump quirk this is BXNN instead, jumping to XNN + VX
+                let offset_register = if self.quirks.jump_uses_vx {
+                    (nnn >> 8) as usize
+                } else {
+                    0
+                };
+                self.pc = (self.registers[offset_register] as u16) + nnn;
+            }
+            Instruction::Random { x, nn } => {
+                // CXNN (VX = rand & NN)
+                // generates a random number, binary ANDs it with the value NN, and
+                // puts the result in VX
+                let x = x as usize;
+                self.registers[x] = self.rng.next_u8() & nn;
+            }
+            Instruction::SkipKeyPress { x } => {
+                // EX9E
+                // skip one instruction (increment PC by 2) if the key corresponding to
+                // the value in VX is pressed.
+                let x = x as usize;
+                let vx = self.registers[x];
+                let key = self.key_pressed(vx)?;
+                if key {
+                    self.skip_next();
                 }
-                Instruction::SkipKeyPress => {
-                    // EX9E
-                    // skip one instruction (increment PC by 2) if the key corresponding to
-                    // the value in VX is pressed.
-                    let x = nibble_2 as usize;
-                    let vx = self.registers[x];
-                    let key = self.keys[vx as usize];
-                    if key {
-                        self.pc += 2;
-                    }
+            }
+            Instruction::SkipKeyRelease { x } => {
+                // EXA1
+                // skips if the key corresponding to the value in VX is not pressed.
+                let x = x as usize;
+                let vx = self.registers[x];
+                let key = self.key_pressed(vx)?;
+                if !key {
+                    self.skip_next();
                 }
-                Instruction::SkipKeyRelease => {
-                    // EXA1
-                    // skips if the key corresponding to the value in VX is not pressed.
-                    let x = nibble_2 as usize;
-                    let vx = self.registers[x];
-                    let key = self.keys[vx as usize];
-                    if !key {
-                        self.pc += 2;
+            }
+            Instruction::SetVXToTimer { x } => {
+                // FX07 VX = Delay Timer
+                let x = x as usize;
+                self.registers[x] = self.delay_timer;
+            }
+            Instruction::SetTimerToVX { x } => {
+                // FX15 Delay Timer = VX
+                let x = x as usize;
+                self.delay_timer = self.registers[x];
+            }
+            Instruction::SetSoundTimerToVX { x } => {
+                // FX18 Sound Timer = VX
+                let x = x as usize;
+                self.sound_timer = self.registers[x];
+            }
+            Instruction::AddToIndex { x } => {
+                // FX1E (I += VX)
+                let x = x as usize;
+                let vx = self.registers[x] as u16;
+                self.index_register = self.index_register.wrapping_add(vx);
+            }
+            Instruction::WaitKey { x } => {
+                // FX0A
+                // stops executing instructions and waits for key input
+                // (or loops forever, unless a key is pressed).
+                // Also, If a key is pressed while this instruction is waiting for input,
+                // its hexadecimal value will be put in VX and execution continues.
+                let x = x as usize;
+                let mut pressed = false;
+                for i in 0..self.keys.len() {
+                    if self.keys[i] {
+                        self.registers[x] = i as u8;
+                        pressed = true;
+                        break;
                     }
                 }
-                Instruction::SetVXToTimer => {
-                    // FX07 VX = Delay Timer
-                    let x = nibble_2 as usize;
-                    self.registers[x] = self.delay_timer;
-                }
-                Instruction::SetTimerToVX => {
-                    // FX15 Delay Timer = VX
-                    let x = nibble_2 as usize;
-                    self.delay_timer = self.registers[x];
+                if !pressed {
+                    self.pc = self.pc.wrapping_sub(2);
                 }
-                Instruction::SetSoundTimerToVX => {
-                    // FX18 Sound Timer = VX
-                    let x = nibble_2 as usize;
-                    self.sound_timer = self.registers[x];
-                }
-                Instruction::AddToIndex => {
-                    // FX1E (I += VX)
-                    let x = nibble_2 as usize;
-                    let vx = self.registers[x] as u16;
-                    self.index_register = self.index_register.wrapping_add(vx);
-                }
-                Instruction::WaitKey => {
-                    // FX0A
-                    // stops executing instructions and waits for key input
-                    // (or loops forever, unless a key is pressed).
-                    // Also, If a key is pressed while this instruction is waiting for input,
-                    // its hexadecimal value will be put in VX and execution continues.
-                    let x = nibble_2 as usize;
-                    let mut pressed = false;
-                    for i in 0..self.keys.len() {
-                        if self.keys[i] {
-                            self.registers[x] = i as u8;
-                            pressed = true;
-                            break;
-                        }
-                    }
-                    if !pressed {
-                        self.pc -= 2;
-                    }
-                }
-                Instruction::FontCharacter => {
-                    // FX29 (I = FONT)
-                    // Set index register to the address of the hexadecimal character
-                    // in VX
-                    let x = nibble_2 as usize;
-                    let c = self.registers[x] as u16;
-                    self.index_register = c * 5;
+            }
+            Instruction::FontCharacter { x } => {
+                // FX29 (I = FONT)
+                // Set index register to the address of the hexadecimal character
+                // in VX
+                let x = x as usize;
+                let c = self.registers[x] as u16;
+                self.index_register = c * 5;
+            }
+            Instruction::BinaryCodedDecimalConversion { x } => {
+                // FX33
+                // Take the number in VX (which is one byte, so it can be any number
+                // from 0 to 255) and convert it to three decimal digits, storing these
+                // digits in memory at the address in the index register I.
+                let x = x as usize;
+                let vx = self.registers[x] as f32;
+
+                let hundreds_digit = (vx / 100.0).floor() as u8;
+                let tens_digit = ((vx / 10.0) % 10.0).floor() as u8;
+                // Fetch the ones digit by tossing the hundreds and the tens
+                let ones_digit = (vx % 10.0) as u8;
+
+                let i = self.index_register as usize;
+                self.check_memory(i + 2)?;
+                self.memory[i] = hundreds_digit;
+                self.memory[i + 1] = tens_digit;
+                self.memory[i + 2] = ones_digit;
+            }
+            Instruction::StoreMemory { x } => {
+                // FX55 (Store V0 to VX)
+                // The value of each variable register from V0 to VX inclusive
+                // (if X is 0, then only V0) will be stored in successive memory addresses,
+                // starting with the one that’s stored in I.
+                // V0 will be stored at the address in I, V1 will be stored in I + 1,
+                // and so on, until VX is stored in I + X.
+                let x = x as usize;
+                let i = self.index_register as usize;
+                self.check_memory(i + x)?;
+                for idx in 0..=x {
+                    self.memory[i + idx] = self.registers[idx];
                 }
-                Instruction::BinaryCodedDecimalConversion => {
-                    // FX33
-                    // Take the number in VX (which is one byte, so it can be any number
-                    // from 0 to 255) and convert it to three decimal digits, storing these
-                    // digits in memory at the address in the index register I.
-                    let x = nibble_2 as usize;
-                    let vx = self.registers[x] as f32;
-
-                    let hundreds_digit = (vx / 100.0).floor() as u8;
-                    let tens_digit = ((vx / 10.0) % 10.0).floor() as u8;
-                    // Fetch the ones digit by tossing the hundreds and the tens
-                    let ones_digit = (vx % 10.0) as u8;
-
-                    self.memory[self.index_register as usize] = hundreds_digit;
-                    self.memory[(self.index_register + 1) as usize] = tens_digit;
-                    self.memory[(self.index_register + 2) as usize] = ones_digit;
+                self.advance_index(x);
+            }
+            Instruction::LoadMemory { x } => {
+                // FX65 (Load V0 to VX)
+                // Does the opposite of Store; it takes the value stored at the memory
+                // addresses and loads them into the variable registers instead.
+                let x = x as usize;
+                let i = self.index_register as usize;
+                self.check_memory(i + x)?;
+                for idx in 0..=x {
+                    self.registers[idx] = self.memory[i + idx];
                 }
-                Instruction::StoreMemory => {
-                    // FX55 (Store V0 to VX)
-                    // The value of each variable register from V0 to VX inclusive
-                    // (if X is 0, then only V0) will be stored in successive memory addresses,
-                    // starting with the one that’s stored in I.
-                    // V0 will be stored at the address in I, V1 will be stored in I + 1,
-                    // and so on, until VX is stored in I + X.
-                    let x = nibble_2 as usize;
-                    let i = self.index_register as usize;
-                    for idx in 0..=x {
-                        self.memory[i + idx] = self.registers[idx];
-                    }
+                self.advance_index(x);
+            }
+            Instruction::ScrollDown { n } => {
+                // 00CN
+                self.scroll(0, n as isize);
+            }
+            Instruction::ScrollRight => {
+                // 00FB
+                self.scroll(4, 0);
+            }
+            Instruction::ScrollLeft => {
+                // 00FC
+                self.scroll(-4, 0);
+            }
+            Instruction::Exit => {
+                // 00FD
+                self.exited = true;
+            }
+            Instruction::LowResolution => {
+                // 00FE
+                self.set_resolution(false);
+            }
+            Instruction::HighResolution => {
+                // 00FF
+                self.set_resolution(true);
+            }
+            Instruction::BigFontCharacter { x } => {
+                // FX30 (I = BIG FONT)
+                // Like FX29 but points at the 8x10 digits
+                let x = x as usize;
+                let c = (self.registers[x] & 0xF) as usize;
+                self.index_register = (BIG_FONT_ADDR + c * 10) as u16;
+            }
+            Instruction::SaveFlags { x } => {
+                // FX75 (Store V0 to VX in RPL flags)
+                let x = x as usize;
+                self.rpl_flags[..=x].copy_from_slice(&self.registers[..=x]);
+            }
+            Instruction::LoadFlags { x } => {
+                // FX85 (Load V0 to VX from RPL flags)
+                let x = x as usize;
+                self.registers[..=x].copy_from_slice(&self.rpl_flags[..=x]);
+            }
+            Instruction::ScrollUp { n } => {
+                // 00DN
+                self.scroll(0, -(n as isize));
+            }
+            Instruction::SaveRange { x, y } => {
+                // 5XY2 (Store VX to VY)
+                // Like FX55 but for any range of registers, in reverse order when
+                // X > Y. I is left unchanged.
+                let x = x as usize;
+                let y = y as usize;
+                let i = self.index_register as usize;
+                self.check_memory(i + x.abs_diff(y))?;
+                for offset in 0..=x.abs_diff(y) {
+                    let reg = if x <= y { x + offset } else { x - offset };
+                    self.memory[i + offset] = self.registers[reg];
                 }
-                Instruction::LoadMemory => {
-                    // FX65 (Load V0 to VX)
-                    // Does the opposite of Store; it takes the value stored at the memory
-                    // addresses and loads them into the variable registers instead.
-                    let x = nibble_2 as usize;
-                    let i = self.index_register as usize;
-                    for idx in 0..=x {
-                        self.registers[idx] = self.memory[i + idx];
-                    }
+            }
+            Instruction::LoadRange { x, y } => {
+                // 5XY3 (Load VX to VY)
+                let x = x as usize;
+                let y = y as usize;
+                let i = self.index_register as usize;
+                self.check_memory(i + x.abs_diff(y))?;
+                for offset in 0..=x.abs_diff(y) {
+                    let reg = if x <= y { x + offset } else { x - offset };
+                    self.registers[reg] = self.memory[i + offset];
                 }
             }
-        } else {
-            eprintln!("Unimplemented opcode: {:#04x?}", opcode);
+            Instruction::LongIndex => {
+                // F000 NNNN (I = NNNN)
+                // The address is stored in the two bytes following the opcode
+                let nnnn = self.fetch()?;
+                self.index_register = nnnn;
+            }
+            Instruction::SelectPlanes { n } => {
+                // FN01
+                self.planes = n & 0b11;
+            }
+            Instruction::LoadAudio => {
+                // F002 (Audio pattern = 16 bytes at I)
+                let i = self.index_register as usize;
+                self.check_memory(i + AUDIO_PATTERN_SIZE - 1)?;
+                let mut pattern = [0; AUDIO_PATTERN_SIZE];
+                pattern.copy_from_slice(&self.memory[i..i + AUDIO_PATTERN_SIZE]);
+                self.audio_pattern = Some(pattern);
+            }
+            Instruction::SetPitch { x } => {
+                // FX3A (Pitch = VX)
+                let x = x as usize;
+                self.pitch = self.registers[x];
+            }
         }
+        Ok(instruction)
     }
 }
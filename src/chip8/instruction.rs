@@ -0,0 +1,302 @@
+use super::Platform;
+use std::fmt;
+
+// Every instruction the interpreter understands along with its operands.
+// X and Y are register numbers, N is a 4 bit, NN an 8 bit and NNN a 12 bit constant.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Instruction {
+    Nop,                                    // 0000
+    ClearScreen,                            // 00E0
+    Jump { nnn: u16 },                      // 1NNN
+    CallSubroutine { nnn: u16 },            // 2NNN
+    ReturnSubroutine,                       // 00EE
+    SkipVXEqualNN { x: u8, nn: u8 },        // 3XNN
+    SkipVXNotEqualNN { x: u8, nn: u8 },     // 4XNN
+    SkipVXEqualVY { x: u8, y: u8 },         // 5XY0
+    SkipVXNotEqualVY { x: u8, y: u8 },      // 9XY0
+    SetVXToNN { x: u8, nn: u8 },            // 6XNN
+    AddNNToVX { x: u8, nn: u8 },            // 7XNN
+    SetVXToVY { x: u8, y: u8 },             // 8XY0
+    Or { x: u8, y: u8 },                    // 8XY1 (VX |= VY)
+    And { x: u8, y: u8 },                   // 8XY2 (VX &= VY)
+    Xor { x: u8, y: u8 },                   // 8XY3 (VX ^= VY)
+    Add { x: u8, y: u8 },                   // 8XY4 (VX += VY)
+    SubtractFrom { x: u8, y: u8 },          // 8XY5 (VX -= VY)
+    Subtract { x: u8, y: u8 },              // 8XY7 (VX = VY - VX)
+    RightShift { x: u8, y: u8 },            // 8XY6 (VX >>= 1)
+    LeftShift { x: u8, y: u8 },             // 8XYE ( VX <<= 1)
+    SetIndex { nnn: u16 },                  // ANNN (I = NNN)
+    JumpWithOffset { nnn: u16 },            // BNNN (JMP V0 + NNN)
+    Random { x: u8, nn: u8 },               // CXNN (VX = rand & NN)
+    Draw { x: u8, y: u8, n: u8 },           // DXYN (DXY0 draws a 16x16 sprite)
+    SkipKeyPress { x: u8 },                 // EX9E
+    SkipKeyRelease { x: u8 },               // EXA1
+    SetVXToTimer { x: u8 },                 // FX07 VX = Delay Timer
+    SetTimerToVX { x: u8 },                 // FX15 Delay Timer = VX
+    SetSoundTimerToVX { x: u8 },            // FX18 Sound Timer = VX
+    AddToIndex { x: u8 },                   // FX1E (I += VX)
+    WaitKey { x: u8 },                      // FX0A
+    FontCharacter { x: u8 },                // FX29 (I = FONT)
+    BinaryCodedDecimalConversion { x: u8 }, // FX33
+    StoreMemory { x: u8 },                  // FX55 (Store V0 to VX)
+    LoadMemory { x: u8 },                   // FX65 (Load V0 to VX)
+    // SUPER-CHIP 1.1
+    ScrollDown { n: u8 },       // 00CN
+    ScrollRight,                // 00FB
+    ScrollLeft,                 // 00FC
+    Exit,                       // 00FD
+    LowResolution,              // 00FE
+    HighResolution,             // 00FF
+    BigFontCharacter { x: u8 }, // FX30 (I = BIG FONT)
+    SaveFlags { x: u8 },        // FX75 (Store V0 to VX in RPL flags)
+    LoadFlags { x: u8 },        // FX85 (Load V0 to VX from RPL flags)
+    // XO-CHIP
+    ScrollUp { n: u8 },         // 00DN
+    SaveRange { x: u8, y: u8 }, // 5XY2 (Store VX to VY)
+    LoadRange { x: u8, y: u8 }, // 5XY3 (Load VX to VY)
+    LongIndex,                  // F000 NNNN (I = NNNN, NNNN is the next word)
+    SelectPlanes { n: u8 },     // FN01
+    LoadAudio,                  // F002 (Audio pattern = 16 bytes at I)
+    SetPitch { x: u8 },         // FX3A (Pitch = VX)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DecodeError {
+    pub opcode: u16,
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown opcode {:#06X}", self.opcode)
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl Instruction {
+    pub fn decode(opcode: u16) -> Result<Self, DecodeError> {
+        /*
+            1010 0010 0010 1010 -> 41514 -> OP Example
+            First Nibble (half-byte) -> 1010 to Hexadecimal
+            Second Nibble ...
+        */
+        let nibble_1 = (opcode >> 12) & 0b1111;
+        let nibble_2 = (opcode >> 8) & 0b1111;
+        let nibble_3 = (opcode >> 4) & 0b1111;
+        let nibble_4 = opcode & 0b1111;
+
+        let x = nibble_2 as u8;
+        let y = nibble_3 as u8;
+        let n = nibble_4 as u8;
+        let nn = (opcode & 0xFF) as u8;
+        let nnn = opcode & 0xFFF;
+
+        let instruction = match (nibble_1, nibble_2, nibble_3, nibble_4) {
+            (0, 0, 0, 0) => Self::Nop,                                  // 0000
+            (0, 0, 0xE, 0) => Self::ClearScreen,                        // 00E0
+            (0, 0, 0xE, 0xE) => Self::ReturnSubroutine,                 // 00EE
+            (0, 0, 0xC, _) => Self::ScrollDown { n },                   // 00CN
+            (0, 0, 0xD, _) => Self::ScrollUp { n },                     // 00DN
+            (0, 0, 0xF, 0xB) => Self::ScrollRight,                      // 00FB
+            (0, 0, 0xF, 0xC) => Self::ScrollLeft,                       // 00FC
+            (0, 0, 0xF, 0xD) => Self::Exit,                             // 00FD
+            (0, 0, 0xF, 0xE) => Self::LowResolution,                    // 00FE
+            (0, 0, 0xF, 0xF) => Self::HighResolution,                   // 00FF
+            (1, _, _, _) => Self::Jump { nnn },                         // 1NNN
+            (2, _, _, _) => Self::CallSubroutine { nnn },               // 2NNN
+            (3, _, _, _) => Self::SkipVXEqualNN { x, nn },              // 3XNN
+            (4, _, _, _) => Self::SkipVXNotEqualNN { x, nn },           // 4XNN
+            (5, _, _, 0) => Self::SkipVXEqualVY { x, y },               // 5XY0
+            (5, _, _, 2) => Self::SaveRange { x, y },                   // 5XY2
+            (5, _, _, 3) => Self::LoadRange { x, y },                   // 5XY3
+            (6, _, _, _) => Self::SetVXToNN { x, nn },                  // 6XNN
+            (7, _, _, _) => Self::AddNNToVX { x, nn },                  // 7XNN
+            (8, _, _, 0) => Self::SetVXToVY { x, y },                   // 8XY0
+            (8, _, _, 1) => Self::Or { x, y },                          // 8XY1
+            (8, _, _, 2) => Self::And { x, y },                         // 8XY2
+            (8, _, _, 3) => Self::Xor { x, y },                         // 8XY3
+            (8, _, _, 4) => Self::Add { x, y },                         // 8XY4
+            (8, _, _, 5) => Self::SubtractFrom { x, y },                // 8XY5
+            (8, _, _, 6) => Self::RightShift { x, y },                  // 8XY6
+            (8, _, _, 7) => Self::Subtract { x, y },                    // 8XY7
+            (8, _, _, 0xE) => Self::LeftShift { x, y },                 // 8XYE
+            (9, _, _, 0) => Self::SkipVXNotEqualVY { x, y },            // 9XY0
+            (0xA, _, _, _) => Self::SetIndex { nnn },                   // ANNN
+            (0xB, _, _, _) => Self::JumpWithOffset { nnn },             // BNNN
+            (0xC, _, _, _) => Self::Random { x, nn },                   // CXNN
+            (0xD, _, _, _) => Self::Draw { x, y, n },                   // DXYN
+            (0xE, _, 9, 0xE) => Self::SkipKeyPress { x },               // EX9E
+            (0xE, _, 0xA, 1) => Self::SkipKeyRelease { x },             // EXA1
+            (0xF, 0, 0, 0) => Self::LongIndex,                          // F000 NNNN
+            (0xF, _, 0, 1) => Self::SelectPlanes { n: x },              // FN01
+            (0xF, 0, 0, 2) => Self::LoadAudio,                          // F002
+            (0xF, _, 0, 7) => Self::SetVXToTimer { x },                 // FX07
+            (0xF, _, 0, 0xA) => Self::WaitKey { x },                    // FX0A
+            (0xF, _, 1, 5) => Self::SetTimerToVX { x },                 // FX15
+            (0xF, _, 1, 8) => Self::SetSoundTimerToVX { x },            // FX18
+            (0xF, _, 1, 0xE) => Self::AddToIndex { x },                 // FX1E
+            (0xF, _, 2, 9) => Self::FontCharacter { x },                // FX29
+            (0xF, _, 3, 0) => Self::BigFontCharacter { x },             // FX30
+            (0xF, _, 3, 3) => Self::BinaryCodedDecimalConversion { x }, // FX33
+            (0xF, _, 3, 0xA) => Self::SetPitch { x },                   // FX3A
+            (0xF, _, 5, 5) => Self::StoreMemory { x },                  // FX55
+            (0xF, _, 6, 5) => Self::LoadMemory { x },                   // FX65
+            (0xF, _, 7, 5) => Self::SaveFlags { x },                    // FX75
+            (0xF, _, 8, 5) => Self::LoadFlags { x },                    // FX85
+            _ => return Err(DecodeError { opcode }),
+        };
+        Ok(instruction)
+    }
+
+    // The inverse of decode. Operands are masked to the bits their field can hold.
+    pub fn encode(&self) -> u16 {
+        let xy = |op: u16, x: u8, y: u8, n: u16| {
+            op | ((x as u16 & 0xF) << 8) | ((y as u16 & 0xF) << 4) | n
+        };
+        let xnn = |op: u16, x: u8, nn: u8| op | ((x as u16 & 0xF) << 8) | nn as u16;
+        let fx = |x: u8, low: u16| 0xF000 | ((x as u16 & 0xF) << 8) | low;
+        match *self {
+            Self::Nop => 0x0000,
+            Self::ClearScreen => 0x00E0,
+            Self::ReturnSubroutine => 0x00EE,
+            Self::ScrollDown { n } => 0x00C0 | (n as u16 & 0xF),
+            Self::ScrollUp { n } => 0x00D0 | (n as u16 & 0xF),
+            Self::ScrollRight => 0x00FB,
+            Self::ScrollLeft => 0x00FC,
+            Self::Exit => 0x00FD,
+            Self::LowResolution => 0x00FE,
+            Self::HighResolution => 0x00FF,
+            Self::Jump { nnn } => 0x1000 | (nnn & 0xFFF),
+            Self::CallSubroutine { nnn } => 0x2000 | (nnn & 0xFFF),
+            Self::SkipVXEqualNN { x, nn } => xnn(0x3000, x, nn),
+            Self::SkipVXNotEqualNN { x, nn } => xnn(0x4000, x, nn),
+            Self::SkipVXEqualVY { x, y } => xy(0x5000, x, y, 0),
+            Self::SaveRange { x, y } => xy(0x5000, x, y, 2),
+            Self::LoadRange { x, y } => xy(0x5000, x, y, 3),
+            Self::SetVXToNN { x, nn } => xnn(0x6000, x, nn),
+            Self::AddNNToVX { x, nn } => xnn(0x7000, x, nn),
+            Self::SetVXToVY { x, y } => xy(0x8000, x, y, 0),
+            Self::Or { x, y } => xy(0x8000, x, y, 1),
+            Self::And { x, y } => xy(0x8000, x, y, 2),
+            Self::Xor { x, y } => xy(0x8000, x, y, 3),
+            Self::Add { x, y } => xy(0x8000, x, y, 4),
+            Self::SubtractFrom { x, y } => xy(0x8000, x, y, 5),
+            Self::RightShift { x, y } => xy(0x8000, x, y, 6),
+            Self::Subtract { x, y } => xy(0x8000, x, y, 7),
+            Self::LeftShift { x, y } => xy(0x8000, x, y, 0xE),
+            Self::SkipVXNotEqualVY { x, y } => xy(0x9000, x, y, 0),
+            Self::SetIndex { nnn } => 0xA000 | (nnn & 0xFFF),
+            Self::JumpWithOffset { nnn } => 0xB000 | (nnn & 0xFFF),
+            Self::Random { x, nn } => xnn(0xC000, x, nn),
+            Self::Draw { x, y, n } => xy(0xD000, x, y, n as u16 & 0xF),
+            Self::SkipKeyPress { x } => xnn(0xE000, x, 0x9E),
+            Self::SkipKeyRelease { x } => xnn(0xE000, x, 0xA1),
+            Self::LongIndex => 0xF000,
+            Self::SelectPlanes { n } => fx(n, 0x01),
+            Self::LoadAudio => 0xF002,
+            Self::SetVXToTimer { x } => fx(x, 0x07),
+            Self::WaitKey { x } => fx(x, 0x0A),
+            Self::SetTimerToVX { x } => fx(x, 0x15),
+            Self::SetSoundTimerToVX { x } => fx(x, 0x18),
+            Self::AddToIndex { x } => fx(x, 0x1E),
+            Self::FontCharacter { x } => fx(x, 0x29),
+            Self::BigFontCharacter { x } => fx(x, 0x30),
+            Self::BinaryCodedDecimalConversion { x } => fx(x, 0x33),
+            Self::SetPitch { x } => fx(x, 0x3A),
+            Self::StoreMemory { x } => fx(x, 0x55),
+            Self::LoadMemory { x } => fx(x, 0x65),
+            Self::SaveFlags { x } => fx(x, 0x75),
+            Self::LoadFlags { x } => fx(x, 0x85),
+        }
+    }
+
+    // The instruction set extension the instruction belongs to, None for plain CHIP-8
+    pub fn extension(&self) -> Option<Platform> {
+        match self {
+            Self::ScrollDown { .. }
+            | Self::ScrollRight
+            | Self::ScrollLeft
+            | Self::Exit
+            | Self::LowResolution
+            | Self::HighResolution
+            | Self::BigFontCharacter { .. }
+            | Self::SaveFlags { .. }
+            | Self::LoadFlags { .. } => Some(Platform::SuperChip),
+            Self::ScrollUp { .. }
+            | Self::SaveRange { .. }
+            | Self::LoadRange { .. }
+            | Self::LongIndex
+            | Self::SelectPlanes { .. }
+            | Self::LoadAudio
+            | Self::SetPitch { .. } => Some(Platform::XoChip),
+            _ => None,
+        }
+    }
+
+    // Size of the instruction in bytes. Only F000 NNNN is followed by an extra word.
+    pub fn size(&self) -> u16 {
+        match self {
+            Self::LongIndex => 4,
+            _ => 2,
+        }
+    }
+}
+
+// Classic mnemonics, as in Cowgod's Chip-8 technical reference
+impl fmt::Display for Instruction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Self::Nop => write!(f, "NOP"),
+            Self::ClearScreen => write!(f, "CLS"),
+            Self::ReturnSubroutine => write!(f, "RET"),
+            Self::ScrollDown { n } => write!(f, "SCD {}", n),
+            Self::ScrollUp { n } => write!(f, "SCU {}", n),
+            Self::ScrollRight => write!(f, "SCR"),
+            Self::ScrollLeft => write!(f, "SCL"),
+            Self::Exit => write!(f, "EXIT"),
+            Self::LowResolution => write!(f, "LOW"),
+            Self::HighResolution => write!(f, "HIGH"),
+            Self::Jump { nnn } => write!(f, "JP {:#05X}", nnn),
+            Self::CallSubroutine { nnn } => write!(f, "CALL {:#05X}", nnn),
+            Self::SkipVXEqualNN { x, nn } => write!(f, "SE V{:X}, {:#04X}", x, nn),
+            Self::SkipVXNotEqualNN { x, nn } => write!(f, "SNE V{:X}, {:#04X}", x, nn),
+            Self::SkipVXEqualVY { x, y } => write!(f, "SE V{:X}, V{:X}", x, y),
+            Self::SaveRange { x, y } => write!(f, "LD [I], V{:X}-V{:X}", x, y),
+            Self::LoadRange { x, y } => write!(f, "LD V{:X}-V{:X}, [I]", x, y),
+            Self::SetVXToNN { x, nn } => write!(f, "LD V{:X}, {:#04X}", x, nn),
+            Self::AddNNToVX { x, nn } => write!(f, "ADD V{:X}, {:#04X}", x, nn),
+            Self::SetVXToVY { x, y } => write!(f, "LD V{:X}, V{:X}", x, y),
+            Self::Or { x, y } => write!(f, "OR V{:X}, V{:X}", x, y),
+            Self::And { x, y } => write!(f, "AND V{:X}, V{:X}", x, y),
+            Self::Xor { x, y } => write!(f, "XOR V{:X}, V{:X}", x, y),
+            Self::Add { x, y } => write!(f, "ADD V{:X}, V{:X}", x, y),
+            Self::SubtractFrom { x, y } => write!(f, "SUB V{:X}, V{:X}", x, y),
+            Self::RightShift { x, y } => write!(f, "SHR V{:X}, V{:X}", x, y),
+            Self::Subtract { x, y } => write!(f, "SUBN V{:X}, V{:X}", x, y),
+            Self::LeftShift { x, y } => write!(f, "SHL V{:X}, V{:X}", x, y),
+            Self::SkipVXNotEqualVY { x, y } => write!(f, "SNE V{:X}, V{:X}", x, y),
+            Self::SetIndex { nnn } => write!(f, "LD I, {:#05X}", nnn),
+            Self::JumpWithOffset { nnn } => write!(f, "JP V0, {:#05X}", nnn),
+            Self::Random { x, nn } => write!(f, "RND V{:X}, {:#04X}", x, nn),
+            Self::Draw { x, y, n } => write!(f, "DRW V{:X}, V{:X}, {}", x, y, n),
+            Self::SkipKeyPress { x } => write!(f, "SKP V{:X}", x),
+            Self::SkipKeyRelease { x } => write!(f, "SKNP V{:X}", x),
+            Self::LongIndex => write!(f, "LD I, LONG"),
+            Self::SelectPlanes { n } => write!(f, "PLANE {}", n),
+            Self::LoadAudio => write!(f, "AUDIO"),
+            Self::SetVXToTimer { x } => write!(f, "LD V{:X}, DT", x),
+            Self::WaitKey { x } => write!(f, "LD V{:X}, K", x),
+            Self::SetTimerToVX { x } => write!(f, "LD DT, V{:X}", x),
+            Self::SetSoundTimerToVX { x } => write!(f, "LD ST, V{:X}", x),
+            Self::AddToIndex { x } => write!(f, "ADD I, V{:X}", x),
+            Self::FontCharacter { x } => write!(f, "LD F, V{:X}", x),
+            Self::BigFontCharacter { x } => write!(f, "LD HF, V{:X}", x),
+            Self::BinaryCodedDecimalConversion { x } => write!(f, "LD B, V{:X}", x),
+            Self::SetPitch { x } => write!(f, "PITCH V{:X}", x),
+            Self::StoreMemory { x } => write!(f, "LD [I], V{:X}", x),
+            Self::LoadMemory { x } => write!(f, "LD V{:X}, [I]", x),
+            Self::SaveFlags { x } => write!(f, "LD R, V{:X}", x),
+            Self::LoadFlags { x } => write!(f, "LD V{:X}, R", x),
+        }
+    }
+}
@@ -0,0 +1,254 @@
+use super::{
+    Chip8, Platform, Quirks, Rng, AUDIO_PATTERN_SIZE, HIRES_SCREEN_HEIGHT, HIRES_SCREEN_WIDTH,
+    KEYS_COUNT, REGISTERS_COUNT, RPL_FLAGS_COUNT, STACK_SIZE,
+};
+use std::fmt;
+
+// Save states start with a magic number and a version so files written by older
+// builds can still be read, or rejected with a clear error, after the format changes.
+const MAGIC: &[u8; 4] = b"C8ST";
+// Version 2 added the random number generator's state
+const VERSION: u16 = 2;
+const SCREEN_SIZE: usize = HIRES_SCREEN_WIDTH * HIRES_SCREEN_HEIGHT;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StateError {
+    BadMagic,                // Not a save state
+    UnsupportedVersion(u16), // Written by a newer version of the emulator
+    Truncated,               // The data ends before the state does
+    Invalid(&'static str),   // A field holds a value the machine cannot be in
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StateError::BadMagic => write!(f, "not a save state"),
+            StateError::UnsupportedVersion(version) => {
+                write!(f, "unsupported save state version {}", version)
+            }
+            StateError::Truncated => write!(f, "save state is truncated"),
+            StateError::Invalid(what) => write!(f, "save state has an invalid {}", what),
+        }
+    }
+}
+
+impl std::error::Error for StateError {}
+
+impl Chip8 {
+    // Serialise the whole machine, including the platform and quirks it runs with.
+    // The tracer is not part of the machine and is left out.
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(self.memory.len() + self.screen.len() + 128);
+        out.extend_from_slice(MAGIC);
+        out.extend_from_slice(&VERSION.to_le_bytes());
+
+        out.push(platform_to_u8(self.platform));
+        out.push(quirks_to_u8(self.quirks));
+
+        out.extend_from_slice(&self.pc.to_le_bytes());
+        out.extend_from_slice(&self.index_register.to_le_bytes());
+        out.extend_from_slice(&self.registers);
+        for addr in self.stack {
+            out.extend_from_slice(&addr.to_le_bytes());
+        }
+        out.push(self.sp);
+        let keys = self
+            .keys
+            .iter()
+            .enumerate()
+            .fold(0u16, |mask, (idx, pressed)| {
+                mask | ((*pressed as u16) << idx)
+            });
+        out.extend_from_slice(&keys.to_le_bytes());
+        out.push(self.delay_timer);
+        out.push(self.sound_timer);
+
+        out.push(self.hires as u8);
+        out.push(self.planes);
+        out.push(self.exited as u8);
+        out.push(self.waiting_for_vblank as u8);
+        out.extend_from_slice(&self.rpl_flags);
+        match self.audio_pattern {
+            Some(pattern) => {
+                out.push(1);
+                out.extend_from_slice(&pattern);
+            }
+            None => {
+                out.push(0);
+                out.extend_from_slice(&[0; AUDIO_PATTERN_SIZE]);
+            }
+        }
+        out.push(self.pitch);
+
+        out.extend_from_slice(&(self.memory.len() as u32).to_le_bytes());
+        out.extend_from_slice(&self.memory);
+        out.extend_from_slice(&self.screen);
+        out.extend_from_slice(&self.rng.state().to_le_bytes());
+        out
+    }
+
+    // Restore a state written by save_state. Nothing is changed if the state is rejected.
+    pub fn load_state(&mut self, data: &[u8]) -> Result<(), StateError> {
+        let mut reader = Reader { data, pos: 0 };
+        if reader.bytes(MAGIC.len())? != MAGIC {
+            return Err(StateError::BadMagic);
+        }
+        let version = reader.u16()?;
+        if version == 0 || version > VERSION {
+            return Err(StateError::UnsupportedVersion(version));
+        }
+
+        let platform = platform_from_u8(reader.u8()?)?;
+        let quirks = quirks_from_u8(reader.u8()?);
+
+        let pc = reader.u16()?;
+        let index_register = reader.u16()?;
+        let mut registers = [0; REGISTERS_COUNT];
+        registers.copy_from_slice(reader.bytes(REGISTERS_COUNT)?);
+        let mut stack = [0; STACK_SIZE];
+        for addr in stack.iter_mut() {
+            *addr = reader.u16()?;
+        }
+        let sp = reader.u8()?;
+        if sp as usize > STACK_SIZE {
+            return Err(StateError::Invalid("stack pointer"));
+        }
+        let key_mask = reader.u16()?;
+        let mut keys = [false; KEYS_COUNT];
+        for (idx, pressed) in keys.iter_mut().enumerate() {
+            *pressed = key_mask & (1 << idx) != 0;
+        }
+        let delay_timer = reader.u8()?;
+        let sound_timer = reader.u8()?;
+
+        let hires = reader.u8()? != 0;
+        let planes = reader.u8()?;
+        if planes > 0b11 {
+            return Err(StateError::Invalid("plane selection"));
+        }
+        let exited = reader.u8()? != 0;
+        let waiting_for_vblank = reader.u8()? != 0;
+        let mut rpl_flags = [0; RPL_FLAGS_COUNT];
+        rpl_flags.copy_from_slice(reader.bytes(RPL_FLAGS_COUNT)?);
+        let has_pattern = reader.u8()? != 0;
+        let mut pattern = [0; AUDIO_PATTERN_SIZE];
+        pattern.copy_from_slice(reader.bytes(AUDIO_PATTERN_SIZE)?);
+        let pitch = reader.u8()?;
+
+        let memory_size = reader.u32()? as usize;
+        if memory_size != platform.memory_size() {
+            return Err(StateError::Invalid("memory size"));
+        }
+        let memory = reader.bytes(memory_size)?.to_vec();
+        let mut screen = [0; SCREEN_SIZE];
+        screen.copy_from_slice(reader.bytes(SCREEN_SIZE)?);
+        // Older states keep whatever generator the machine is running with
+        let rng = if version >= 2 {
+            Rng::new(reader.u64()?)
+        } else {
+            self.rng
+        };
+
+        self.platform = platform;
+        self.quirks = quirks;
+        self.pc = pc;
+        self.index_register = index_register;
+        self.registers = registers;
+        self.stack = stack;
+        self.sp = sp;
+        self.keys = keys;
+        self.delay_timer = delay_timer;
+        self.sound_timer = sound_timer;
+        self.hires = hires;
+        self.planes = planes;
+        self.exited = exited;
+        self.waiting_for_vblank = waiting_for_vblank;
+        self.rpl_flags = rpl_flags;
+        self.audio_pattern = has_pattern.then_some(pattern);
+        self.pitch = pitch;
+        self.memory = memory;
+        self.screen = screen;
+        self.rng = rng;
+        Ok(())
+    }
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, len: usize) -> Result<&'a [u8], StateError> {
+        let end = self.pos.checked_add(len).ok_or(StateError::Truncated)?;
+        let bytes = self.data.get(self.pos..end).ok_or(StateError::Truncated)?;
+        self.pos = end;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, StateError> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16, StateError> {
+        let bytes = self.bytes(2)?;
+        Ok(u16::from_le_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Result<u32, StateError> {
+        let bytes = self.bytes(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn u64(&mut self) -> Result<u64, StateError> {
+        let mut bytes = [0; 8];
+        bytes.copy_from_slice(self.bytes(8)?);
+        Ok(u64::from_le_bytes(bytes))
+    }
+}
+
+fn platform_to_u8(platform: Platform) -> u8 {
+    match platform {
+        Platform::Chip8 => 0,
+        Platform::SuperChip => 1,
+        Platform::XoChip => 2,
+    }
+}
+
+fn platform_from_u8(value: u8) -> Result<Platform, StateError> {
+    match value {
+        0 => Ok(Platform::Chip8),
+        1 => Ok(Platform::SuperChip),
+        2 => Ok(Platform::XoChip),
+        _ => Err(StateError::Invalid("platform")),
+    }
+}
+
+// One bit per quirk, with ones added later at the top so older states read the same
+fn quirks_to_u8(quirks: Quirks) -> u8 {
+    [
+        quirks.shift_uses_vy,
+        quirks.jump_uses_vx,
+        quirks.memory_increments_index,
+        quirks.vf_reset,
+        quirks.clip_sprites,
+        quirks.display_wait,
+        quirks.memory_increment_by_x,
+    ]
+    .iter()
+    .enumerate()
+    .fold(0, |mask, (bit, on)| mask | ((*on as u8) << bit))
+}
+
+fn quirks_from_u8(mask: u8) -> Quirks {
+    let bit = |n: u8| mask & (1 << n) != 0;
+    Quirks {
+        shift_uses_vy: bit(0),
+        jump_uses_vx: bit(1),
+        memory_increments_index: bit(2),
+        vf_reset: bit(3),
+        clip_sprites: bit(4),
+        display_wait: bit(5),
+        memory_increment_by_x: bit(6),
+    }
+}
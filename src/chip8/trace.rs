@@ -0,0 +1,87 @@
+use super::{Instruction, REGISTERS_COUNT};
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+// Everything the CPU did in one tick, handed to the Tracer after the instruction ran
+#[derive(Debug, Clone, Copy)]
+pub struct TraceEvent {
+    pub pc: u16,
+    pub opcode: u16,
+    pub instruction: Instruction,
+    pub registers_before: [u8; REGISTERS_COUNT],
+    pub registers_after: [u8; REGISTERS_COUNT],
+    pub index_before: u16,
+    pub index_after: u16,
+}
+
+impl TraceEvent {
+    // The registers the instruction changed as (register, old value, new value)
+    pub fn register_deltas(&self) -> impl Iterator<Item = (usize, u8, u8)> + '_ {
+        self.registers_before
+            .iter()
+            .zip(self.registers_after.iter())
+            .enumerate()
+            .filter(|(_, (before, after))| before != after)
+            .map(|(idx, (before, after))| (idx, *before, *after))
+    }
+}
+
+impl fmt::Display for TraceEvent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // 0x208: 7A01  ADD VA, 0x01  VA 02->03
+        write!(
+            f,
+            "{:#05X}: {:04X}  {:<20}",
+            self.pc,
+            self.opcode,
+            self.instruction.to_string()
+        )?;
+        for (idx, before, after) in self.register_deltas() {
+            write!(f, " V{:X} {:02X}->{:02X}", idx, before, after)?;
+        }
+        if self.index_before != self.index_after {
+            write!(f, " I {:04X}->{:04X}", self.index_before, self.index_after)?;
+        }
+        Ok(())
+    }
+}
+
+// Receives every executed instruction. Nothing is traced unless a tracer is set
+// on the Chip8, so the interpreter pays nothing for tracing by default.
+// Tracers are Send so a Chip8 can still be moved to another thread.
+pub trait Tracer: fmt::Debug + Send {
+    fn trace(&mut self, event: &TraceEvent);
+}
+
+// Prints one line per instruction to stdout
+#[derive(Debug, Default)]
+pub struct StdoutTracer;
+
+impl Tracer for StdoutTracer {
+    fn trace(&mut self, event: &TraceEvent) {
+        // A closed stdout should not stop the emulation
+        let _ = writeln!(io::stdout().lock(), "{}", event);
+    }
+}
+
+// Writes one line per instruction to a file, buffered as traces get large quickly
+#[derive(Debug)]
+pub struct FileTracer {
+    writer: BufWriter<File>,
+}
+
+impl FileTracer {
+    pub fn create<P: AsRef<Path>>(path: P) -> io::Result<Self> {
+        Ok(Self {
+            writer: BufWriter::new(File::create(path)?),
+        })
+    }
+}
+
+impl Tracer for FileTracer {
+    fn trace(&mut self, event: &TraceEvent) {
+        let _ = writeln!(self.writer, "{}", event);
+    }
+}
@@ -0,0 +1,69 @@
+use std::time::Duration;
+
+// The delay and sound timers always count down at 60 Hz
+pub const TIMER_HZ: u64 = 60;
+// 15 instructions per 60 Hz frame, a speed most CHIP-8 games are happy with
+pub const DEFAULT_INSTRUCTIONS_PER_SECOND: u64 = 900;
+// After a long stall (a dragged window, a breakpoint in a host debugger) only
+// this many frames are caught up instead of running everything that was missed
+const MAX_CATCH_UP_FRAMES: u64 = 15;
+const NANOS_PER_SECOND: u64 = 1_000_000_000;
+
+// Turns elapsed wall time into 60 Hz emulated frames, so the speed of the
+// emulation doesn't depend on how often the frontend gets to run, and hands
+// out the instructions of each frame so a rate that isn't a multiple of 60
+// still averages out exactly.
+#[derive(Debug, Clone)]
+pub struct Clock {
+    instructions_per_second: u64,
+    // Elapsed nanoseconds times TIMER_HZ that haven't made up a whole frame yet
+    frame_remainder: u64,
+    // Instructions times TIMER_HZ that haven't made up a whole instruction yet
+    instruction_remainder: u64,
+}
+
+impl Clock {
+    pub fn new(instructions_per_second: u64) -> Self {
+        Self {
+            instructions_per_second,
+            frame_remainder: 0,
+            instruction_remainder: 0,
+        }
+    }
+
+    pub fn instructions_per_second(&self) -> u64 {
+        self.instructions_per_second
+    }
+
+    // Account for `elapsed` wall time, returning how many frames are now due
+    pub fn advance(&mut self, elapsed: Duration) -> u64 {
+        let elapsed = elapsed
+            .as_nanos()
+            .min((MAX_CATCH_UP_FRAMES * NANOS_PER_SECOND / TIMER_HZ) as u128)
+            as u64;
+        self.frame_remainder += elapsed * TIMER_HZ;
+        let frames = self.frame_remainder / NANOS_PER_SECOND;
+        self.frame_remainder %= NANOS_PER_SECOND;
+        frames
+    }
+
+    // Wall time left until advance() hands out the next frame
+    pub fn until_next_frame(&self) -> Duration {
+        let remaining = NANOS_PER_SECOND - self.frame_remainder;
+        Duration::from_nanos(remaining.div_ceil(TIMER_HZ))
+    }
+
+    // Number of instructions to run before the next timer tick
+    pub fn frame_instructions(&mut self) -> usize {
+        self.instruction_remainder += self.instructions_per_second;
+        let instructions = self.instruction_remainder / TIMER_HZ;
+        self.instruction_remainder %= TIMER_HZ;
+        instructions as usize
+    }
+}
+
+impl Default for Clock {
+    fn default() -> Self {
+        Self::new(DEFAULT_INSTRUCTIONS_PER_SECOND)
+    }
+}
@@ -0,0 +1,304 @@
+// The config file: global defaults followed by per-ROM overrides, keyed by
+// the SHA-1 of the ROM so a game is recognised whatever its file is called.
+//
+//     scale = 10
+//     palette = "amber"
+//     phosphor = 100
+//
+//     [rom.0123456789abcdef0123456789abcdef01234567]
+//     name = "Some Game"    # only for people reading the file
+//     platform = "schip"
+//     ipf = 30
+//     keys = ["Up = 2", "Down = 8", "Left = 4", "Right = 6"]
+//     buttons = ["dpup = 2", "dpdown = 8", "dpleft = 4", "dpright = 6"]
+//
+// It's read as TOML; any other table or setting is an error.
+use crate::chip8::{Platform, Quirks};
+use crate::keymap::Keymap;
+use crate::palette::Palette;
+use crate::phosphor::MAX_PERSISTENCE_MS;
+use crate::sha1::sha1_hex;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap};
+use std::env;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use toml::{Spanned, Value};
+
+// Largest window scale, a 6400 x 3200 window
+pub const MAX_SCALE: u32 = 100;
+
+// Settings shared by the config file and the command line. Each layer only
+// sets what it mentions; `or` fills in the rest from the layer below.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Settings {
+    pub scale: Option<u32>,
+    pub instructions_per_second: Option<u64>,
+    pub platform: Option<Platform>,
+    pub quirks: Option<Quirks>,
+    pub palette: Option<Palette>,
+    // Milliseconds unlit pixels take to fade out, 0 to turn it off
+    pub phosphor: Option<u32>,
+    pub keymap: Option<Keymap>,
+    // Host keys bound on top of the keymap as (name, hex key). Unlike the
+    // other settings these add up, the upper layer's binding winning.
+    pub keys: Vec<(String, usize)>,
+    // Controller buttons bound on top of the default ones, added up like keys
+    pub buttons: Vec<(String, usize)>,
+    pub seed: Option<u64>,
+    pub fullscreen: Option<bool>,
+}
+
+impl Settings {
+    pub fn or(self, fallback: Settings) -> Settings {
+        Settings {
+            scale: self.scale.or(fallback.scale),
+            instructions_per_second: self
+                .instructions_per_second
+                .or(fallback.instructions_per_second),
+            platform: self.platform.or(fallback.platform),
+            quirks: self.quirks.or(fallback.quirks),
+            palette: self.palette.or(fallback.palette),
+            phosphor: self.phosphor.or(fallback.phosphor),
+            keymap: self.keymap.or(fallback.keymap),
+            keys: fallback.keys.into_iter().chain(self.keys).collect(),
+            buttons: fallback.buttons.into_iter().chain(self.buttons).collect(),
+            seed: self.seed.or(fallback.seed),
+            fullscreen: self.fullscreen.or(fallback.fullscreen),
+        }
+    }
+
+    // The keymap with the extra keys bound on top
+    pub fn keymap_with_keys(&self) -> Keymap {
+        let mut keymap = self.keymap.clone().unwrap_or_default();
+        for (name, key) in &self.keys {
+            keymap.bind(name, *key);
+        }
+        keymap
+    }
+
+    // The default controller buttons with the extra ones bound on top
+    pub fn button_map(&self) -> Keymap {
+        let mut buttons = Keymap::buttons();
+        for (name, key) in &self.buttons {
+            buttons.bind(name, *key);
+        }
+        buttons
+    }
+
+    fn set(&mut self, key: &str, value: Value) -> Result<(), String> {
+        match key {
+            "scale" => self.scale = Some(integer(value, 1, MAX_SCALE as i64)? as u32),
+            "ipf" => {
+                self.instructions_per_second = Some(integer(value, 1, u32::MAX as i64)? as u64 * 60)
+            }
+            "ips" => {
+                self.instructions_per_second = Some(integer(value, 1, u32::MAX as i64)? as u64)
+            }
+            "platform" => {
+                self.platform = Some(
+                    Platform::from_name(&string(value)?)
+                        .ok_or("platform must be one of: chip8, schip, xochip")?,
+                )
+            }
+            "quirks" => {
+                self.quirks = Some(
+                    Quirks::from_name(&string(value)?)
+                        .ok_or("quirks must be one of: vip, chip48, schip, xochip")?,
+                )
+            }
+            "palette" => {
+                let text = match value {
+                    Value::Array(colors) => colors
+                        .into_iter()
+                        .map(string)
+                        .collect::<Result<Vec<_>, _>>()?
+                        .join(","),
+                    value => string(value)?,
+                };
+                self.palette = Some(
+                    Palette::parse(&text)
+                        .ok_or("palette must be a palette name or 2 or 4 #RRGGBB colours")?,
+                )
+            }
+            "phosphor" => {
+                self.phosphor = Some(integer(value, 0, MAX_PERSISTENCE_MS as i64)? as u32)
+            }
+            "keymap" => {
+                self.keymap = Some(
+                    Keymap::parse(&string(value)?)
+                        .ok_or("keymap must be 16 different keys, for hex keys 0 to F")?,
+                )
+            }
+            "keys" => self.keys.extend(bindings(value, "host key")?),
+            "buttons" => self.buttons.extend(bindings(value, "button")?),
+            "seed" => self.seed = Some(integer(value, 0, i64::MAX)? as u64),
+            "fullscreen" => self.fullscreen = Some(boolean(value)?),
+            _ if value.is_table() => {
+                return Err(format!("unknown table [{}], expected [rom.SHA1]", key))
+            }
+            _ => return Err(format!("unknown setting '{}'", key)),
+        }
+        Ok(())
+    }
+}
+
+// An array of "name = hex key" strings
+fn bindings(value: Value, what: &str) -> Result<Vec<(String, usize)>, String> {
+    let Value::Array(items) = value else {
+        return Err(format!("expected an array of \"{} = hex key\"", what));
+    };
+    items
+        .into_iter()
+        .map(|item| {
+            let binding = string(item)?;
+            Keymap::parse_binding(&binding)
+                .ok_or_else(|| format!("'{}' should be a {} = hex key binding", binding, what))
+        })
+        .collect()
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Syntax { line: usize, message: String },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(err) => write!(f, "{}", err),
+            ConfigError::Syntax { line, message } => write!(f, "line {}: {}", line, message),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+// The settings of a section, each with where its value is in the file
+type Section = BTreeMap<String, Spanned<Value>>;
+
+// The [rom.SHA1] tables. The defaults are read separately as a plain Section,
+// since flattening them in here would lose their positions.
+#[derive(Deserialize)]
+struct RomSections {
+    #[serde(default)]
+    rom: BTreeMap<Spanned<String>, Section>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Config {
+    pub defaults: Settings,
+    // Keyed by the lowercase hex SHA-1 of the ROM
+    pub roms: HashMap<String, Settings>,
+}
+
+impl Config {
+    pub fn load(path: &Path) -> Result<Self, ConfigError> {
+        let text = fs::read_to_string(path).map_err(ConfigError::Io)?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, ConfigError> {
+        let line = |offset: usize| text[..offset].matches('\n').count() + 1;
+        let toml_error = |err: toml::de::Error| ConfigError::Syntax {
+            line: err.span().map_or(1, |span| line(span.start)),
+            message: err.message().to_string(),
+        };
+        let defaults: Section = toml::from_str(text).map_err(toml_error)?;
+        let sections: RomSections = toml::from_str(text).map_err(toml_error)?;
+
+        // Every setting with the ROM whose section it's in, in file order so
+        // the first mistake is the one reported
+        let mut settings = Vec::new();
+        for (key, value) in defaults {
+            if key != "rom" {
+                settings.push((None, key, value));
+            }
+        }
+        let mut config = Config::default();
+        let mut roms: Vec<_> = sections.rom.into_iter().collect();
+        roms.sort_by_key(|(hash, _)| hash.span().start);
+        for (hash, section) in roms {
+            let at = line(hash.span().start);
+            let syntax = |message: String| ConfigError::Syntax { line: at, message };
+            let hash = hash.into_inner().to_ascii_lowercase();
+            if hash.len() != 40 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+                return Err(syntax(format!("'{}' is not a SHA-1 of a ROM", hash)));
+            }
+            if config.roms.contains_key(&hash) {
+                return Err(syntax(format!("rom.{} is defined twice", hash)));
+            }
+            config.roms.insert(hash.clone(), Settings::default());
+            for (key, value) in section {
+                settings.push((Some(hash.clone()), key, value));
+            }
+        }
+        settings.sort_by_key(|(_, _, value)| value.span().start);
+
+        for (hash, key, value) in settings {
+            let at = line(value.span().start);
+            let syntax = |message: String| ConfigError::Syntax { line: at, message };
+            let value = value.into_inner();
+            match hash {
+                // Names are only there to tell the sections apart
+                Some(_) if key == "name" => {
+                    string(value).map_err(syntax)?;
+                }
+                Some(hash) => config
+                    .roms
+                    .get_mut(&hash)
+                    .unwrap()
+                    .set(&key, value)
+                    .map_err(syntax)?,
+                None => config.defaults.set(&key, value).map_err(syntax)?,
+            }
+        }
+        Ok(config)
+    }
+
+    // Everything the config file says about `rom`, its own section first
+    pub fn settings_for(&self, rom: &[u8]) -> Settings {
+        self.rom_settings(rom).or(self.defaults.clone())
+    }
+
+    // Only what the section for `rom` says, if it has one
+    pub fn rom_settings(&self, rom: &[u8]) -> Settings {
+        self.roms.get(&sha1_hex(rom)).cloned().unwrap_or_default()
+    }
+}
+
+// $XDG_CONFIG_HOME/rust-chip8/config.toml, falling back to ~/.config,
+// or %APPDATA%\rust-chip8\config.toml on Windows
+pub fn default_path() -> Option<PathBuf> {
+    let dir = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+    Some(dir.join("rust-chip8").join("config.toml"))
+}
+
+fn string(value: Value) -> Result<String, String> {
+    match value {
+        Value::String(text) => Ok(text),
+        _ => Err("expected a string".to_string()),
+    }
+}
+
+fn integer(value: Value, min: i64, max: i64) -> Result<i64, String> {
+    match value {
+        Value::Integer(n) if (min..=max).contains(&n) => Ok(n),
+        Value::Integer(n) => Err(format!("{} is not between {} and {}", n, min, max)),
+        _ => Err("expected a number".to_string()),
+    }
+}
+
+fn boolean(value: Value) -> Result<bool, String> {
+    match value {
+        Value::Boolean(b) => Ok(b),
+        _ => Err("expected true or false".to_string()),
+    }
+}
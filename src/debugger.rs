@@ -0,0 +1,418 @@
+// Command-line debugger. Frontends feed it lines of input and ask it before every
+// instruction whether execution should stop; it only drives the Chip8 through
+// its public API.
+use crate::chip8::{Chip8, Instruction};
+use std::collections::BTreeSet;
+use std::fmt;
+use std::fmt::Write as _;
+
+const HELP: &str = "Commands:
+    s, step [N]             Execute N instructions (default 1)
+    c, continue             Run until a breakpoint is hit
+    b, break [ADDR]         Break when PC reaches ADDR, or list breakpoints
+    bo, breakop PATTERN     Break before opcodes matching PATTERN, ? matches any digit (D???, 8??6)
+    d, delete ADDR|PATTERN  Remove a breakpoint
+    r, regs                 Show registers, I, PC, the stack and timers
+    x, mem ADDR [LEN]       Hex dump LEN bytes of memory (default 64)
+    w, write ADDR BYTE...   Write bytes to memory
+    set REG VALUE           Set V0-VF, I, PC, DT or ST
+    l, list [ADDR] [N]      Disassemble N instructions around ADDR (default PC)
+    q, quit                 Quit the emulator
+Addresses, bytes and register values are hexadecimal, counts and lengths decimal.
+An empty line repeats the last command.";
+
+// Instructions shown before the current one by `list`
+const LIST_CONTEXT: u16 = 4;
+const DEFAULT_LIST_COUNT: usize = 10;
+const DEFAULT_DUMP_LEN: usize = 64;
+
+// An opcode with some of its hex digits left free. D??? matches every draw.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct OpcodePattern {
+    value: u16,
+    mask: u16,
+}
+
+impl OpcodePattern {
+    pub fn parse(text: &str) -> Option<Self> {
+        if text.len() != 4 {
+            return None;
+        }
+        let mut value = 0;
+        let mut mask = 0;
+        for c in text.chars() {
+            value <<= 4;
+            mask <<= 4;
+            if c != '?' {
+                value |= c.to_digit(16)? as u16;
+                mask |= 0xF;
+            }
+        }
+        Some(Self { value, mask })
+    }
+
+    pub fn matches(&self, opcode: u16) -> bool {
+        opcode & self.mask == self.value
+    }
+}
+
+impl fmt::Display for OpcodePattern {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for shift in [12, 8, 4, 0] {
+            if (self.mask >> shift) & 0xF == 0 {
+                write!(f, "?")?;
+            } else {
+                write!(f, "{:X}", (self.value >> shift) & 0xF)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+// Why execution stopped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    Step,
+    Breakpoint(u16),
+    Opcode(OpcodePattern),
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Stop::Step => write!(f, "Stepped"),
+            Stop::Breakpoint(addr) => write!(f, "Breakpoint at {:#05X}", addr),
+            Stop::Opcode(pattern) => write!(f, "Opcode breakpoint {}", pattern),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Debugger {
+    paused: bool,
+    quit: bool,
+    // Instructions left to run before stopping again, when stepping
+    steps_left: Option<u32>,
+    // Set when execution resumes, so a breakpoint on the current instruction doesn't fire again
+    resuming: bool,
+    breakpoints: BTreeSet<u16>,
+    opcode_breakpoints: BTreeSet<OpcodePattern>,
+    last_command: String,
+}
+
+impl Debugger {
+    // Starts paused, before the first instruction
+    pub fn new() -> Self {
+        Self {
+            paused: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    pub fn quit_requested(&self) -> bool {
+        self.quit
+    }
+
+    // Called before every instruction while not paused. Returns why execution
+    // has to stop, if it does.
+    pub fn before_tick(&mut self, chip8: &Chip8) -> Option<Stop> {
+        if self.paused {
+            return Some(Stop::Step);
+        }
+        // Nothing executes while the CPU waits for the display
+        if chip8.is_waiting_for_vblank() || chip8.has_exited() {
+            return None;
+        }
+        let resuming = std::mem::take(&mut self.resuming);
+        if let Some(steps) = self.steps_left {
+            if steps == 0 {
+                return Some(self.pause(Stop::Step));
+            }
+            self.steps_left = Some(steps - 1);
+        }
+        if resuming {
+            return None;
+        }
+        let pc = chip8.pc();
+        if self.breakpoints.contains(&pc) {
+            return Some(self.pause(Stop::Breakpoint(pc)));
+        }
+        let opcode = read_word(chip8, pc)?;
+        if let Some(pattern) = self
+            .opcode_breakpoints
+            .iter()
+            .find(|pattern| pattern.matches(opcode))
+        {
+            let stop = Stop::Opcode(*pattern);
+            return Some(self.pause(stop));
+        }
+        None
+    }
+
+    fn pause(&mut self, stop: Stop) -> Stop {
+        self.paused = true;
+        self.steps_left = None;
+        stop
+    }
+
+    fn resume(&mut self, steps: Option<u32>) {
+        self.paused = false;
+        self.resuming = true;
+        self.steps_left = steps;
+    }
+
+    // The instruction about to run, as shown when execution stops
+    pub fn location(&self, chip8: &Chip8) -> String {
+        disassemble_line(chip8, chip8.pc(), true)
+    }
+
+    // Run one line of input and return what to print
+    pub fn execute(&mut self, chip8: &mut Chip8, line: &str) -> String {
+        let line = if line.trim().is_empty() {
+            self.last_command.clone()
+        } else {
+            line.trim().to_string()
+        };
+        self.last_command.clone_from(&line);
+        let mut words = line.split_whitespace();
+        let Some(command) = words.next() else {
+            return String::new();
+        };
+        let args: Vec<&str> = words.collect();
+        let result = match command {
+            "s" | "step" => self.step(&args),
+            "c" | "continue" => {
+                self.resume(None);
+                Ok(String::new())
+            }
+            "b" | "break" => self.break_command(&args),
+            "bo" | "breakop" => self.breakop_command(&args),
+            "d" | "delete" => self.delete_command(&args),
+            "r" | "regs" => Ok(registers(chip8)),
+            "x" | "mem" => memory_dump(chip8, &args),
+            "w" | "write" => write_command(chip8, &args),
+            "set" => set_command(chip8, &args),
+            "l" | "list" => list_command(chip8, &args),
+            "h" | "help" => Ok(format!("{}\n", HELP)),
+            "q" | "quit" => {
+                self.quit = true;
+                Ok(String::new())
+            }
+            _ => Err(format!("Unknown command '{}', try help", command)),
+        };
+        match result {
+            Ok(out) => out,
+            Err(message) => format!("{}\n", message),
+        }
+    }
+
+    fn step(&mut self, args: &[&str]) -> Result<String, String> {
+        let steps = match args.first() {
+            Some(n) => n.parse().map_err(|_| format!("Not a count: {}", n))?,
+            None => 1,
+        };
+        self.resume(Some(steps));
+        Ok(String::new())
+    }
+
+    fn break_command(&mut self, args: &[&str]) -> Result<String, String> {
+        match args.first() {
+            Some(addr) => {
+                let addr = parse_address(addr)?;
+                self.breakpoints.insert(addr);
+                Ok(format!("Breakpoint at {:#05X}\n", addr))
+            }
+            None => {
+                let mut out = String::new();
+                for addr in &self.breakpoints {
+                    let _ = writeln!(out, "Breakpoint at {:#05X}", addr);
+                }
+                for pattern in &self.opcode_breakpoints {
+                    let _ = writeln!(out, "Opcode breakpoint {}", pattern);
+                }
+                if out.is_empty() {
+                    out.push_str("No breakpoints\n");
+                }
+                Ok(out)
+            }
+        }
+    }
+
+    fn breakop_command(&mut self, args: &[&str]) -> Result<String, String> {
+        let text = args
+            .first()
+            .ok_or("breakop expects a pattern such as D???")?;
+        let pattern = OpcodePattern::parse(text).ok_or(format!("Not a pattern: {}", text))?;
+        self.opcode_breakpoints.insert(pattern);
+        Ok(format!("Opcode breakpoint {}\n", pattern))
+    }
+
+    fn delete_command(&mut self, args: &[&str]) -> Result<String, String> {
+        let text = args
+            .first()
+            .ok_or("delete expects an address or a pattern")?;
+        let removed = if text.contains('?') {
+            OpcodePattern::parse(text).is_some_and(|p| self.opcode_breakpoints.remove(&p))
+        } else {
+            self.breakpoints.remove(&parse_address(text)?)
+        };
+        if removed {
+            Ok(format!("Deleted {}\n", text))
+        } else {
+            Err(format!("No breakpoint {}", text))
+        }
+    }
+}
+
+fn read_word(chip8: &Chip8, addr: u16) -> Option<u16> {
+    let bytes = chip8.memory().get(addr as usize..addr as usize + 2)?;
+    Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+}
+
+fn disassemble_line(chip8: &Chip8, addr: u16, current: bool) -> String {
+    let marker = if current { "=>" } else { "  " };
+    match read_word(chip8, addr) {
+        Some(opcode) => {
+            let text = match Instruction::decode(opcode) {
+                Ok(Instruction::LongIndex) => match read_word(chip8, addr.wrapping_add(2)) {
+                    Some(nnnn) => format!("LD I, {:#06X}", nnnn),
+                    None => Instruction::LongIndex.to_string(),
+                },
+                Ok(instruction) => instruction.to_string(),
+                Err(_) => "???".to_string(),
+            };
+            format!("{} {:#05X}  {:04X}  {}", marker, addr, opcode, text)
+        }
+        None => format!("{} {:#05X}  out of memory", marker, addr),
+    }
+}
+
+fn registers(chip8: &Chip8) -> String {
+    let mut out = String::new();
+    for (x, value) in chip8.registers().iter().enumerate() {
+        let separator = if x % 8 == 7 { '\n' } else { ' ' };
+        let _ = write!(out, "V{:X}={:02X}{}", x, value, separator);
+    }
+    let _ = writeln!(
+        out,
+        "I={:04X} PC={:04X} DT={:02X} ST={:02X}",
+        chip8.index_register(),
+        chip8.pc(),
+        chip8.delay_timer(),
+        chip8.sound_timer()
+    );
+    let stack: Vec<String> = chip8
+        .stack()
+        .iter()
+        .map(|addr| format!("{:04X}", addr))
+        .collect();
+    let _ = writeln!(out, "stack=[{}]", stack.join(" "));
+    out
+}
+
+fn memory_dump(chip8: &Chip8, args: &[&str]) -> Result<String, String> {
+    let addr = parse_address(args.first().ok_or("mem expects an address")?)? as usize;
+    let len = match args.get(1) {
+        Some(len) => len.parse().map_err(|_| format!("Not a length: {}", len))?,
+        None => DEFAULT_DUMP_LEN,
+    };
+    let memory = chip8.memory();
+    let end = addr.saturating_add(len).min(memory.len());
+    if addr >= end {
+        return Err(format!("{:#05X} is outside memory", addr));
+    }
+    let mut out = String::new();
+    for (row, chunk) in memory[addr..end].chunks(16).enumerate() {
+        let bytes: Vec<String> = chunk.iter().map(|b| format!("{:02X}", b)).collect();
+        let _ = writeln!(out, "{:04X}  {}", addr + row * 16, bytes.join(" "));
+    }
+    Ok(out)
+}
+
+fn write_command(chip8: &mut Chip8, args: &[&str]) -> Result<String, String> {
+    let (addr, bytes) = args
+        .split_first()
+        .ok_or("write expects an address and bytes")?;
+    let addr = parse_address(addr)?;
+    let bytes = bytes
+        .iter()
+        .map(|b| match parse_number(b) {
+            Some(value) if value <= 0xFF => Ok(value as u8),
+            _ => Err(format!("Not a byte: {}", b)),
+        })
+        .collect::<Result<Vec<u8>, String>>()?;
+    if bytes.is_empty() {
+        return Err("write expects at least one byte".to_string());
+    }
+    chip8
+        .write_memory(addr, &bytes)
+        .map_err(|err| err.to_string())?;
+    Ok(format!("Wrote {} bytes at {:#05X}\n", bytes.len(), addr))
+}
+
+fn set_command(chip8: &mut Chip8, args: &[&str]) -> Result<String, String> {
+    let [name, value] = args else {
+        return Err("set expects a register and a value".to_string());
+    };
+    let value = parse_number(value).ok_or(format!("Not a number: {}", value))?;
+    let name = name.to_ascii_uppercase();
+    let byte = || u8::try_from(value).map_err(|_| format!("{} does not fit in a byte", value));
+    match name.as_str() {
+        "I" => chip8.set_index_register(value as u16),
+        "PC" => chip8.set_pc(value as u16),
+        "DT" => chip8.set_delay_timer(byte()?),
+        "ST" => chip8.set_sound_timer(byte()?),
+        _ => {
+            let x = name
+                .strip_prefix('V')
+                .filter(|digit| digit.len() == 1)
+                .and_then(|digit| usize::from_str_radix(digit, 16).ok())
+                .ok_or(format!("Unknown register {}", name))?;
+            chip8.set_register(x, byte()?);
+        }
+    }
+    Ok(format!("{} = {:#X}\n", name, value))
+}
+
+fn list_command(chip8: &Chip8, args: &[&str]) -> Result<String, String> {
+    let pc = chip8.pc();
+    let start = match args.first() {
+        Some(addr) => parse_address(addr)?,
+        None => pc.saturating_sub(LIST_CONTEXT * 2),
+    };
+    let count = match args.get(1) {
+        Some(n) => n.parse().map_err(|_| format!("Not a count: {}", n))?,
+        None => DEFAULT_LIST_COUNT,
+    };
+    // Once around memory is as much as there is to see
+    let max = chip8.memory().len() / 2;
+    if count > max {
+        return Err(format!("list shows at most {} instructions", max));
+    }
+    let mut out = String::new();
+    for idx in 0..count {
+        let addr = start.wrapping_add(idx as u16 * 2);
+        let _ = writeln!(out, "{}", disassemble_line(chip8, addr, addr == pc));
+    }
+    Ok(out)
+}
+
+fn parse_address(text: &str) -> Result<u16, String> {
+    parse_number(text)
+        .filter(|addr| *addr <= 0xFFFF)
+        .map(|addr| addr as u16)
+        .ok_or(format!("Not an address: {}", text))
+}
+
+// Hexadecimal, with or without 0x
+fn parse_number(text: &str) -> Option<u32> {
+    let hex = text
+        .strip_prefix("0x")
+        .or_else(|| text.strip_prefix("0X"))
+        .unwrap_or(text);
+    u32::from_str_radix(hex, 16).ok()
+}
@@ -0,0 +1,340 @@
+// Disassembler built on the decoder the CPU runs. Code is found by following
+// every path execution can take from the entry point; whatever is never
+// reached is written out as data bytes.
+use crate::chip8::{Instruction, Platform, START_ADDR};
+use std::collections::BTreeMap;
+use std::fmt::Write as _;
+
+// Data bytes written per line
+const BYTES_PER_LINE: usize = 8;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Syntax {
+    // Address, opcode and Cowgod's mnemonics
+    #[default]
+    Classic,
+    // Octo source that assembles back to the same bytes
+    Octo,
+}
+
+// What a label points at, in order of precedence when a place is several things
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum LabelKind {
+    Entry,
+    Subroutine,
+    Jump,
+    Data,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Item {
+    Code(Instruction, Option<u16>), // The operand word of F000 NNNN rides along
+    Data(Vec<u8>),
+}
+
+pub fn disassemble(rom: &[u8], origin: u16, platform: Platform, syntax: Syntax) -> String {
+    let disassembler = Disassembler {
+        rom,
+        origin: origin as usize,
+        platform,
+    };
+    let (code, mut labels) = disassembler.trace();
+    let items = disassembler.layout(&code, &labels);
+    // Targets that ended up inside another instruction can't carry a label
+    labels.retain(|addr, _| items.contains_key(addr));
+    let names: BTreeMap<u16, String> = labels
+        .iter()
+        .map(|(addr, kind)| (*addr, label_name(*addr, *kind)))
+        .collect();
+
+    let mut out = String::new();
+    if syntax == Syntax::Octo && origin != START_ADDR {
+        let _ = writeln!(out, ":org {:#05X}", origin);
+    }
+    for (addr, item) in &items {
+        if let Some(name) = names.get(addr) {
+            match syntax {
+                Syntax::Classic => {
+                    let _ = writeln!(out, "{}:", name);
+                }
+                Syntax::Octo => {
+                    let _ = writeln!(out, ": {}", name);
+                }
+            }
+        }
+        let line = match (syntax, item) {
+            (Syntax::Classic, Item::Code(instruction, operand)) => {
+                classic_code(*addr, instruction, *operand)
+            }
+            (Syntax::Classic, Item::Data(bytes)) => classic_data(*addr, bytes),
+            (Syntax::Octo, Item::Code(instruction, operand)) => {
+                octo_code(instruction, *operand, &names)
+            }
+            (Syntax::Octo, Item::Data(bytes)) => octo_data(bytes),
+        };
+        let _ = writeln!(out, "{}", line);
+    }
+    out
+}
+
+struct Disassembler<'a> {
+    rom: &'a [u8],
+    origin: usize,
+    platform: Platform,
+}
+
+impl Disassembler<'_> {
+    fn end(&self) -> usize {
+        self.origin + self.rom.len()
+    }
+
+    fn contains(&self, addr: u16) -> bool {
+        (self.origin..self.end()).contains(&(addr as usize))
+    }
+
+    fn word(&self, addr: usize) -> Option<u16> {
+        let offset = addr.checked_sub(self.origin)?;
+        let bytes = self.rom.get(offset..offset + 2)?;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn instruction(&self, addr: usize) -> Option<Instruction> {
+        let instruction = Instruction::decode(self.word(addr)?).ok()?;
+        // Opcodes the platform doesn't have are data, as they are to the CPU
+        if !self.platform.supports(&instruction) {
+            return None;
+        }
+        if instruction == Instruction::LongIndex && self.word(addr + 2).is_none() {
+            return None;
+        }
+        Some(instruction)
+    }
+
+    // Follow every path from the entry point, collecting instructions and the
+    // addresses other instructions refer to
+    fn trace(&self) -> (BTreeMap<u16, Instruction>, BTreeMap<u16, LabelKind>) {
+        let mut code = BTreeMap::new();
+        let mut labels = BTreeMap::new();
+        let mut label = |addr: u16, kind: LabelKind| {
+            let entry = labels.entry(addr).or_insert(kind);
+            *entry = (*entry).min(kind);
+        };
+        label(self.origin as u16, LabelKind::Entry);
+
+        let mut pending = vec![self.origin];
+        while let Some(addr) = pending.pop() {
+            if code.contains_key(&(addr as u16)) {
+                continue;
+            }
+            let Some(instruction) = self.instruction(addr) else {
+                continue;
+            };
+            code.insert(addr as u16, instruction);
+            let next = addr + instruction.size() as usize;
+            match instruction {
+                Instruction::Jump { nnn } => {
+                    label(nnn, LabelKind::Jump);
+                    pending.push(nnn as usize);
+                }
+                Instruction::CallSubroutine { nnn } => {
+                    label(nnn, LabelKind::Subroutine);
+                    pending.push(nnn as usize);
+                    pending.push(next);
+                }
+                Instruction::ReturnSubroutine | Instruction::Exit => {}
+                Instruction::JumpWithOffset { nnn } => {
+                    // Usually a table of jumps indexed by V0, so take every
+                    // jump that directly follows the base address as code
+                    label(nnn, LabelKind::Jump);
+                    let mut entry = nnn as usize;
+                    while let Some(Instruction::Jump { .. }) = self.instruction(entry) {
+                        pending.push(entry);
+                        entry += 2;
+                    }
+                    pending.push(nnn as usize);
+                }
+                Instruction::SkipVXEqualNN { .. }
+                | Instruction::SkipVXNotEqualNN { .. }
+                | Instruction::SkipVXEqualVY { .. }
+                | Instruction::SkipVXNotEqualVY { .. }
+                | Instruction::SkipKeyPress { .. }
+                | Instruction::SkipKeyRelease { .. } => {
+                    pending.push(next);
+                    pending.push(next + self.skipped_size(next));
+                }
+                Instruction::SetIndex { nnn } => {
+                    if self.contains(nnn) {
+                        label(nnn, LabelKind::Data);
+                    }
+                    pending.push(next);
+                }
+                Instruction::LongIndex => {
+                    if let Some(target) = self.word(addr + 2).filter(|t| self.contains(*t)) {
+                        label(target, LabelKind::Data);
+                    }
+                    pending.push(next);
+                }
+                _ => pending.push(next),
+            }
+        }
+        labels.retain(|addr, _| self.contains(*addr));
+        (code, labels)
+    }
+
+    // Same rule as the CPU: F000 NNNN is skipped whole where it exists
+    fn skipped_size(&self, addr: usize) -> usize {
+        self.instruction(addr)
+            .map_or(2, |instruction| instruction.size() as usize)
+    }
+
+    // Lay the ROM out as instructions and runs of data. A traced instruction that
+    // starts inside the previous one is left as part of it.
+    fn layout(
+        &self,
+        code: &BTreeMap<u16, Instruction>,
+        labels: &BTreeMap<u16, LabelKind>,
+    ) -> BTreeMap<u16, Item> {
+        let mut items = BTreeMap::new();
+        let mut data_start = None;
+        let mut data = Vec::new();
+        let mut addr = self.origin;
+        while addr < self.end() {
+            let instruction = code.get(&(addr as u16)).copied();
+            if instruction.is_some()
+                || (!data.is_empty() && labels.contains_key(&(addr as u16)))
+                || data.len() == BYTES_PER_LINE
+            {
+                if let Some(start) = data_start.take() {
+                    items.insert(start, Item::Data(std::mem::take(&mut data)));
+                }
+            }
+            match instruction {
+                Some(instruction) => {
+                    let operand = match instruction {
+                        Instruction::LongIndex => self.word(addr + 2),
+                        _ => None,
+                    };
+                    items.insert(addr as u16, Item::Code(instruction, operand));
+                    addr += instruction.size() as usize;
+                }
+                None => {
+                    data_start.get_or_insert(addr as u16);
+                    data.push(self.rom[addr - self.origin]);
+                    addr += 1;
+                }
+            }
+        }
+        if let Some(start) = data_start {
+            items.insert(start, Item::Data(data));
+        }
+        items
+    }
+}
+
+fn label_name(addr: u16, kind: LabelKind) -> String {
+    match kind {
+        LabelKind::Entry => "main".to_string(),
+        LabelKind::Subroutine => format!("sub_{:03X}", addr),
+        LabelKind::Jump => format!("label_{:03X}", addr),
+        LabelKind::Data => format!("data_{:03X}", addr),
+    }
+}
+
+fn classic_code(addr: u16, instruction: &Instruction, operand: Option<u16>) -> String {
+    match operand {
+        Some(nnnn) => format!(
+            "{:#05X}  {:04X} {:04X}  LD I, {:#06X}",
+            addr,
+            instruction.encode(),
+            nnnn,
+            nnnn
+        ),
+        None => format!(
+            "{:#05X}  {:04X}  {}",
+            addr,
+            instruction.encode(),
+            instruction
+        ),
+    }
+}
+
+fn classic_data(addr: u16, bytes: &[u8]) -> String {
+    let bytes: Vec<String> = bytes.iter().map(|b| format!("{:#04X}", b)).collect();
+    format!("{:#05X}  DB {}", addr, bytes.join(", "))
+}
+
+fn octo_data(bytes: &[u8]) -> String {
+    let bytes: Vec<String> = bytes.iter().map(|b| format!("{:#04x}", b)).collect();
+    format!("\t{}", bytes.join(" "))
+}
+
+fn octo_code(
+    instruction: &Instruction,
+    operand: Option<u16>,
+    names: &BTreeMap<u16, String>,
+) -> String {
+    let target = |addr: u16| match names.get(&addr) {
+        Some(name) => name.clone(),
+        None => format!("{:#05x}", addr),
+    };
+    let text = match *instruction {
+        // Octo has no way to write 0000, so it goes out as data
+        Instruction::Nop => "0x00 0x00".to_string(),
+        Instruction::ClearScreen => "clear".to_string(),
+        Instruction::ReturnSubroutine => "return".to_string(),
+        Instruction::Jump { nnn } => format!("jump {}", target(nnn)),
+        Instruction::CallSubroutine { nnn } => match names.get(&nnn) {
+            Some(name) => name.clone(),
+            None => format!(":call {:#05x}", nnn),
+        },
+        // A skip runs the next instruction when its condition is false,
+        // so each one is written as `if` with the opposite condition
+        Instruction::SkipVXEqualNN { x, nn } => format!("if v{:x} != {:#04x} then", x, nn),
+        Instruction::SkipVXNotEqualNN { x, nn } => format!("if v{:x} == {:#04x} then", x, nn),
+        Instruction::SkipVXEqualVY { x, y } => format!("if v{:x} != v{:x} then", x, y),
+        Instruction::SkipVXNotEqualVY { x, y } => format!("if v{:x} == v{:x} then", x, y),
+        Instruction::SkipKeyPress { x } => format!("if v{:x} -key then", x),
+        Instruction::SkipKeyRelease { x } => format!("if v{:x} key then", x),
+        Instruction::SetVXToNN { x, nn } => format!("v{:x} := {:#04x}", x, nn),
+        Instruction::AddNNToVX { x, nn } => format!("v{:x} += {:#04x}", x, nn),
+        Instruction::SetVXToVY { x, y } => format!("v{:x} := v{:x}", x, y),
+        Instruction::Or { x, y } => format!("v{:x} |= v{:x}", x, y),
+        Instruction::And { x, y } => format!("v{:x} &= v{:x}", x, y),
+        Instruction::Xor { x, y } => format!("v{:x} ^= v{:x}", x, y),
+        Instruction::Add { x, y } => format!("v{:x} += v{:x}", x, y),
+        Instruction::SubtractFrom { x, y } => format!("v{:x} -= v{:x}", x, y),
+        Instruction::Subtract { x, y } => format!("v{:x} =- v{:x}", x, y),
+        Instruction::RightShift { x, y } => format!("v{:x} >>= v{:x}", x, y),
+        Instruction::LeftShift { x, y } => format!("v{:x} <<= v{:x}", x, y),
+        Instruction::SetIndex { nnn } => format!("i := {}", target(nnn)),
+        Instruction::JumpWithOffset { nnn } => format!("jump0 {}", target(nnn)),
+        Instruction::Random { x, nn } => format!("v{:x} := random {:#04x}", x, nn),
+        Instruction::Draw { x, y, n } => format!("sprite v{:x} v{:x} {}", x, y, n),
+        Instruction::SetVXToTimer { x } => format!("v{:x} := delay", x),
+        Instruction::WaitKey { x } => format!("v{:x} := key", x),
+        Instruction::SetTimerToVX { x } => format!("delay := v{:x}", x),
+        Instruction::SetSoundTimerToVX { x } => format!("buzzer := v{:x}", x),
+        Instruction::AddToIndex { x } => format!("i += v{:x}", x),
+        Instruction::FontCharacter { x } => format!("i := hex v{:x}", x),
+        Instruction::BinaryCodedDecimalConversion { x } => format!("bcd v{:x}", x),
+        Instruction::StoreMemory { x } => format!("save v{:x}", x),
+        Instruction::LoadMemory { x } => format!("load v{:x}", x),
+        Instruction::ScrollDown { n } => format!("scroll-down {}", n),
+        Instruction::ScrollRight => "scroll-right".to_string(),
+        Instruction::ScrollLeft => "scroll-left".to_string(),
+        Instruction::Exit => "exit".to_string(),
+        Instruction::LowResolution => "lores".to_string(),
+        Instruction::HighResolution => "hires".to_string(),
+        Instruction::BigFontCharacter { x } => format!("i := bighex v{:x}", x),
+        Instruction::SaveFlags { x } => format!("saveflags v{:x}", x),
+        Instruction::LoadFlags { x } => format!("loadflags v{:x}", x),
+        Instruction::ScrollUp { n } => format!("scroll-up {}", n),
+        Instruction::SaveRange { x, y } => format!("save v{:x} - v{:x}", x, y),
+        Instruction::LoadRange { x, y } => format!("load v{:x} - v{:x}", x, y),
+        Instruction::LongIndex => format!("i := long {}", target(operand.unwrap_or(0))),
+        Instruction::SelectPlanes { n } => format!("plane {}", n),
+        Instruction::LoadAudio => "audio".to_string(),
+        Instruction::SetPitch { x } => format!("pitch := v{:x}", x),
+    };
+    format!("\t{}", text)
+}
@@ -0,0 +1,428 @@
+// GDB remote serial protocol stub. A client attaches over TCP and can read and
+// write registers and memory, set software breakpoints, step and continue.
+//
+// Registers are numbered V0-VF (0-15), I (16), PC (17), SP (18), DT (19) and
+// ST (20). 16 bit registers are sent little endian, and the layout is also
+// described by the target.xml the stub serves through qXfer:features:read.
+use crate::chip8::{Chip8, Chip8Error};
+use std::collections::{BTreeSet, VecDeque};
+use std::fmt::Write as _;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{TcpListener, TcpStream};
+
+const REGISTER_I: usize = 16;
+const REGISTER_PC: usize = 17;
+const REGISTER_SP: usize = 18;
+const REGISTER_DT: usize = 19;
+const REGISTER_ST: usize = 20;
+const REGISTERS_COUNT: usize = 21;
+// Sent by the client to interrupt a running target
+const INTERRUPT: u8 = 0x03;
+// Signals reported in stop replies
+const SIGINT: u8 = 2;
+const SIGILL: u8 = 4;
+const SIGTRAP: u8 = 5;
+const SIGSEGV: u8 = 11;
+
+const TARGET_XML: &str = r#"<?xml version="1.0"?>
+<!DOCTYPE target SYSTEM "gdb-target.dtd">
+<target version="1.0">
+  <feature name="org.chip8.core">
+    <reg name="v0" bitsize="8" regnum="0"/>
+    <reg name="v1" bitsize="8"/>
+    <reg name="v2" bitsize="8"/>
+    <reg name="v3" bitsize="8"/>
+    <reg name="v4" bitsize="8"/>
+    <reg name="v5" bitsize="8"/>
+    <reg name="v6" bitsize="8"/>
+    <reg name="v7" bitsize="8"/>
+    <reg name="v8" bitsize="8"/>
+    <reg name="v9" bitsize="8"/>
+    <reg name="va" bitsize="8"/>
+    <reg name="vb" bitsize="8"/>
+    <reg name="vc" bitsize="8"/>
+    <reg name="vd" bitsize="8"/>
+    <reg name="ve" bitsize="8"/>
+    <reg name="vf" bitsize="8"/>
+    <reg name="i" bitsize="16" type="data_ptr"/>
+    <reg name="pc" bitsize="16" type="code_ptr"/>
+    <reg name="sp" bitsize="8"/>
+    <reg name="dt" bitsize="8"/>
+    <reg name="st" bitsize="8"/>
+  </feature>
+</target>
+"#;
+
+// What the connection ended with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Session {
+    Detached,
+    Killed,
+    Disconnected,
+}
+
+pub struct GdbStub {
+    stream: TcpStream,
+    breakpoints: BTreeSet<u16>,
+    // Instructions between two 60 Hz timer ticks while the target runs
+    ticks_per_frame: usize,
+    ticks: usize,
+    // Bytes that arrived while the target ran, read before the socket again
+    pending: VecDeque<u8>,
+}
+
+// What the client did while the target was running
+enum Poll {
+    Nothing,
+    Interrupted,
+    Disconnected,
+}
+
+impl GdbStub {
+    // Wait for a client to connect
+    pub fn accept(listener: &TcpListener, ticks_per_frame: usize) -> io::Result<Self> {
+        let (stream, _) = listener.accept()?;
+        stream.set_nodelay(true)?;
+        Ok(Self {
+            stream,
+            breakpoints: BTreeSet::new(),
+            ticks_per_frame: ticks_per_frame.max(1),
+            ticks: 0,
+            pending: VecDeque::new(),
+        })
+    }
+
+    // Serve the client until it detaches, kills the target or goes away
+    pub fn run(&mut self, chip8: &mut Chip8) -> io::Result<Session> {
+        loop {
+            let Some(packet) = self.read_packet()? else {
+                return Ok(Session::Disconnected);
+            };
+            let reply = match packet.as_bytes().first() {
+                Some(b'D') => {
+                    self.send("OK")?;
+                    return Ok(Session::Detached);
+                }
+                Some(b'k') => return Ok(Session::Killed),
+                Some(b'c') | Some(b's') => {
+                    let step = packet.starts_with('s');
+                    match self.resume(chip8, &packet[1..], step)? {
+                        Some(reply) => reply,
+                        // A client that hangs up on a running target is gone
+                        None => return Ok(Session::Disconnected),
+                    }
+                }
+                _ => self.handle(chip8, &packet),
+            };
+            self.send(&reply)?;
+        }
+    }
+
+    // Everything that doesn't run the target
+    fn handle(&mut self, chip8: &mut Chip8, packet: &str) -> String {
+        let mut chars = packet.chars();
+        let command = chars.next();
+        let args = chars.as_str();
+        let reply = match command {
+            Some('?') => Some(stop_reply(SIGTRAP)),
+            Some('g') => Some(
+                (0..REGISTERS_COUNT)
+                    .map(|n| read_register(chip8, n))
+                    .collect(),
+            ),
+            Some('G') => write_registers(chip8, args),
+            Some('p') => usize::from_str_radix(args, 16)
+                .ok()
+                .filter(|n| *n < REGISTERS_COUNT)
+                .map(|n| read_register(chip8, n)),
+            Some('P') => args
+                .split_once('=')
+                .and_then(|(n, value)| Some((usize::from_str_radix(n, 16).ok()?, value)))
+                .and_then(|(n, value)| write_register(chip8, n, value)),
+            Some('m') => read_memory(chip8, args),
+            Some('M') => write_memory(chip8, args),
+            Some('Z') => self.breakpoint(true, args),
+            Some('z') => self.breakpoint(false, args),
+            Some('H') => Some("OK".to_string()),
+            Some('q') => query(args),
+            // Anything else is unsupported, which the empty reply tells the client
+            _ => return String::new(),
+        };
+        reply.unwrap_or_else(|| "E01".to_string())
+    }
+
+    fn breakpoint(&mut self, insert: bool, args: &str) -> Option<String> {
+        let mut fields = args.split(',');
+        // Only software breakpoints (type 0) are supported
+        if fields.next()? != "0" {
+            return Some(String::new());
+        }
+        let addr = u16::from_str_radix(fields.next()?, 16).ok()?;
+        if insert {
+            self.breakpoints.insert(addr);
+        } else {
+            self.breakpoints.remove(&addr);
+        }
+        Some("OK".to_string())
+    }
+
+    // Step one instruction, or run until a breakpoint, an error or an interrupt.
+    // Returns None if the client disconnected while the target was running.
+    fn resume(&mut self, chip8: &mut Chip8, addr: &str, step: bool) -> io::Result<Option<String>> {
+        if let Ok(addr) = u16::from_str_radix(addr, 16) {
+            chip8.set_pc(addr);
+        }
+        if step {
+            return Ok(Some(
+                self.execute(chip8).unwrap_or_else(|| stop_reply(SIGTRAP)),
+            ));
+        }
+        self.stream.set_nonblocking(true)?;
+        let reply = loop {
+            if let Some(reply) = self.execute(chip8) {
+                break Some(reply);
+            }
+            if self.breakpoints.contains(&chip8.pc()) {
+                break Some(stop_reply(SIGTRAP));
+            }
+            // Look for an interrupt once per frame
+            if self.ticks == 0 {
+                match self.poll()? {
+                    Poll::Nothing => {}
+                    Poll::Interrupted => break Some(stop_reply(SIGINT)),
+                    Poll::Disconnected => break None,
+                }
+            }
+        };
+        self.stream.set_nonblocking(false)?;
+        Ok(reply)
+    }
+
+    // Run one instruction, returning the stop reply if the target can't go on
+    fn execute(&mut self, chip8: &mut Chip8) -> Option<String> {
+        // An instruction waiting for the display runs once the frame ends
+        if chip8.is_waiting_for_vblank() {
+            self.end_frame(chip8);
+        }
+        let result = chip8.tick();
+        self.ticks += 1;
+        if self.ticks >= self.ticks_per_frame {
+            self.end_frame(chip8);
+        }
+        match result {
+            _ if chip8.has_exited() => Some("W00".to_string()),
+            Ok(()) => None,
+            Err(Chip8Error::UnknownOpcode(_)) => Some(stop_reply(SIGILL)),
+            Err(_) => Some(stop_reply(SIGSEGV)),
+        }
+    }
+
+    fn end_frame(&mut self, chip8: &mut Chip8) {
+        chip8.tick_timers();
+        self.ticks = 0;
+    }
+
+    // Drain whatever the client sent without blocking. The interrupt byte is
+    // taken out and anything else is kept for the next packet read.
+    fn poll(&mut self) -> io::Result<Poll> {
+        let mut buffer = [0; 256];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Ok(Poll::Disconnected),
+                Ok(len) => self.pending.extend(&buffer[..len]),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err),
+            }
+        }
+        match self.pending.iter().position(|byte| *byte == INTERRUPT) {
+            Some(pos) => {
+                self.pending.remove(pos);
+                Ok(Poll::Interrupted)
+            }
+            None => Ok(Poll::Nothing),
+        }
+    }
+
+    // Read the next $packet#checksum, acknowledging it. Returns None once the client is gone.
+    fn read_packet(&mut self) -> io::Result<Option<String>> {
+        loop {
+            // Skip acknowledgements and stray interrupts until a packet starts
+            match self.read_byte()? {
+                None => return Ok(None),
+                Some(b'$') => {}
+                Some(_) => continue,
+            }
+            let mut data = Vec::new();
+            loop {
+                match self.read_byte()? {
+                    None => return Ok(None),
+                    Some(b'#') => break,
+                    Some(byte) => data.push(byte),
+                }
+            }
+            let (Some(high), Some(low)) = (self.read_byte()?, self.read_byte()?) else {
+                return Ok(None);
+            };
+            let expected = std::str::from_utf8(&[high, low])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+            if expected != Some(checksum(&data)) {
+                self.stream.write_all(b"-")?;
+                continue;
+            }
+            self.stream.write_all(b"+")?;
+            return Ok(Some(String::from_utf8_lossy(&data).into_owned()));
+        }
+    }
+
+    fn read_byte(&mut self) -> io::Result<Option<u8>> {
+        if let Some(byte) = self.pending.pop_front() {
+            return Ok(Some(byte));
+        }
+        let mut byte = [0];
+        match self.stream.read(&mut byte)? {
+            0 => Ok(None),
+            _ => Ok(Some(byte[0])),
+        }
+    }
+
+    fn send(&mut self, data: &str) -> io::Result<()> {
+        let packet = format!("${}#{:02x}", data, checksum(data.as_bytes()));
+        self.stream.write_all(packet.as_bytes())?;
+        self.stream.flush()
+    }
+}
+
+fn checksum(data: &[u8]) -> u8 {
+    data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+}
+
+fn stop_reply(signal: u8) -> String {
+    format!("S{:02x}", signal)
+}
+
+fn query(args: &str) -> Option<String> {
+    let reply = if args.starts_with("Supported") {
+        "PacketSize=1000;qXfer:features:read+".to_string()
+    } else if let Some(range) = args.strip_prefix("Xfer:features:read:target.xml:") {
+        let (offset, len) = range.split_once(',')?;
+        let offset = usize::from_str_radix(offset, 16)
+            .ok()?
+            .min(TARGET_XML.len());
+        let len = usize::from_str_radix(len, 16).ok()?;
+        let end = offset.saturating_add(len).min(TARGET_XML.len());
+        let marker = if end == TARGET_XML.len() { 'l' } else { 'm' };
+        format!("{}{}", marker, &TARGET_XML[offset..end])
+    } else {
+        match args {
+            "Attached" => "1".to_string(),
+            "C" => "QC1".to_string(),
+            "fThreadInfo" => "m1".to_string(),
+            "sThreadInfo" => "l".to_string(),
+            _ => String::new(),
+        }
+    };
+    Some(reply)
+}
+
+fn read_register(chip8: &Chip8, n: usize) -> String {
+    match n {
+        0..=15 => format!("{:02x}", chip8.registers()[n]),
+        REGISTER_I => hex(&chip8.index_register().to_le_bytes()),
+        REGISTER_PC => hex(&chip8.pc().to_le_bytes()),
+        REGISTER_SP => format!("{:02x}", chip8.stack().len()),
+        REGISTER_DT => format!("{:02x}", chip8.delay_timer()),
+        _ => format!("{:02x}", chip8.sound_timer()),
+    }
+}
+
+fn register_size(n: usize) -> usize {
+    match n {
+        REGISTER_I | REGISTER_PC => 2,
+        _ => 1,
+    }
+}
+
+fn write_register(chip8: &mut Chip8, n: usize, value: &str) -> Option<String> {
+    let bytes = unhex(value)?;
+    if n >= REGISTERS_COUNT || bytes.len() != register_size(n) {
+        return None;
+    }
+    match n {
+        0..=15 => chip8.set_register(n, bytes[0]),
+        REGISTER_I => chip8.set_index_register(u16::from_le_bytes([bytes[0], bytes[1]])),
+        REGISTER_PC => chip8.set_pc(u16::from_le_bytes([bytes[0], bytes[1]])),
+        // The stack pointer only moves with calls and returns
+        REGISTER_SP => {
+            if bytes[0] as usize != chip8.stack().len() {
+                return None;
+            }
+        }
+        REGISTER_DT => chip8.set_delay_timer(bytes[0]),
+        REGISTER_ST => chip8.set_sound_timer(bytes[0]),
+        _ => return None,
+    }
+    Some("OK".to_string())
+}
+
+fn write_registers(chip8: &mut Chip8, data: &str) -> Option<String> {
+    let mut offset: usize = 0;
+    for n in 0..REGISTERS_COUNT {
+        let len = register_size(n) * 2;
+        let value = data.get(offset..offset.checked_add(len)?)?;
+        // Leave the stack pointer alone rather than fail the whole write
+        if n != REGISTER_SP {
+            write_register(chip8, n, value)?;
+        }
+        offset += len;
+    }
+    Some("OK".to_string())
+}
+
+fn parse_range(text: &str) -> Option<(usize, usize)> {
+    let (addr, len) = text.split_once(',')?;
+    Some((
+        usize::from_str_radix(addr, 16).ok()?,
+        usize::from_str_radix(len, 16).ok()?,
+    ))
+}
+
+fn read_memory(chip8: &Chip8, args: &str) -> Option<String> {
+    let (addr, len) = parse_range(args)?;
+    let memory = chip8.memory();
+    if addr >= memory.len() {
+        return None;
+    }
+    let end = addr.saturating_add(len).min(memory.len());
+    Some(hex(&memory[addr..end]))
+}
+
+fn write_memory(chip8: &mut Chip8, args: &str) -> Option<String> {
+    let (range, data) = args.split_once(':')?;
+    let (addr, len) = parse_range(range)?;
+    let bytes = unhex(data)?;
+    if bytes.len() != len || addr > u16::MAX as usize {
+        return None;
+    }
+    chip8.write_memory(addr as u16, &bytes).ok()?;
+    Some("OK".to_string())
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 2);
+    for byte in bytes {
+        let _ = write!(out, "{:02x}", byte);
+    }
+    out
+}
+
+fn unhex(text: &str) -> Option<Vec<u8>> {
+    let pairs = text.as_bytes().chunks_exact(2);
+    if !pairs.remainder().is_empty() {
+        return None;
+    }
+    pairs
+        .map(|pair| u8::from_str_radix(std::str::from_utf8(pair).ok()?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,304 @@
+// Runs a ROM without a window or audio so it can be tested on build machines.
+use rust_chip8::chip8::{
+    parse_address, Chip8, Chip8Error, Instruction, Platform, Quirks, ETI_660_START_ADDR, START_ADDR,
+};
+use rust_chip8::gdb::GdbStub;
+use std::env;
+use std::fmt::Write as _;
+use std::fs;
+use std::net::TcpListener;
+use std::process;
+
+const DEFAULT_FRAMES: u32 = 600;
+const DEFAULT_TICKS_PER_FRAME: usize = 15;
+// Frames a scripted key stays down unless told otherwise
+const DEFAULT_PRESS_FRAMES: u32 = 5;
+const USAGE: &str = "Usage: chip8-headless [options] path/to/rom
+
+Options:
+    --frames N                          Run for N frames of 1/60 s (default 600)
+    --until-loop                        Stop early once the ROM jumps to itself (1NNN to NNN)
+    --ipf N                             Instructions per frame (default 15)
+    --platform chip8|schip|xochip       Machine the ROM was written for
+    --quirks vip|chip48|schip|xochip    Override the platform's quirks
+    --load-addr ADDR                    Load the ROM at ADDR instead of 0x200
+    --eti660                            Load the ROM at 0x600 like the ETI-660
+    --seed N                            Seed the random number generator (default 0)
+    --press FRAME:KEY[:FRAMES]          Hold hex KEY from FRAME on for FRAMES frames (default 5)
+    --pbm FILE                          Write the final screen to FILE as a plain PBM image
+    --ascii                             Print the final screen to stdout
+    --gdb PORT                          Wait for a GDB remote protocol client on 127.0.0.1:PORT
+                                        and let it drive the emulator instead of running frames
+
+Exit codes:
+    0   Ran all frames, reached a self-jump or the ROM exited with 00FD
+    1   The CPU stopped with an error
+    2   Bad arguments or the ROM could not be loaded
+    3   --until-loop was given but no self-jump was reached in time";
+
+const EXIT_OK: i32 = 0;
+const EXIT_CPU_ERROR: i32 = 1;
+const EXIT_USAGE: i32 = 2;
+const EXIT_TIMEOUT: i32 = 3;
+
+#[derive(Debug, Clone, Copy)]
+struct KeyPress {
+    frame: u32,
+    key: usize,
+    frames: u32,
+}
+
+impl KeyPress {
+    // FRAME:KEY or FRAME:KEY:FRAMES, with KEY a single hex digit
+    fn parse(text: &str) -> Option<Self> {
+        let mut parts = text.split(':');
+        let frame = parts.next()?.parse().ok()?;
+        let key = usize::from_str_radix(parts.next()?, 16).ok()?;
+        let frames = match parts.next() {
+            Some(frames) => frames.parse().ok()?,
+            None => DEFAULT_PRESS_FRAMES,
+        };
+        if key > 0xF || parts.next().is_some() {
+            return None;
+        }
+        Some(Self { frame, key, frames })
+    }
+
+    fn is_down(&self, frame: u32) -> bool {
+        frame >= self.frame && frame - self.frame < self.frames
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+enum Outcome {
+    FramesDone,
+    SelfJump,
+    Exited,
+    Debugged,
+}
+
+fn main() {
+    let mut args = env::args().skip(1);
+    let mut frames = DEFAULT_FRAMES;
+    let mut until_loop = false;
+    let mut ticks_per_frame = DEFAULT_TICKS_PER_FRAME;
+    let mut platform = Platform::default();
+    let mut quirks = None;
+    let mut load_addr = START_ADDR;
+    // Runs are reproducible unless asked otherwise
+    let mut seed = 0;
+    let mut presses = Vec::new();
+    let mut pbm_path = None;
+    let mut ascii = false;
+    let mut gdb_port = None;
+    let mut rom_file_path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frames" => match args.next().and_then(|a| a.parse().ok()) {
+                Some(n) => frames = n,
+                None => usage_error("--frames expects a number of frames"),
+            },
+            "--until-loop" => until_loop = true,
+            "--ipf" => match args.next().and_then(|a| a.parse().ok()) {
+                Some(n) => ticks_per_frame = n,
+                None => usage_error("--ipf expects a number of instructions"),
+            },
+            "--platform" => match args.next().as_deref().and_then(Platform::from_name) {
+                Some(p) => platform = p,
+                None => usage_error("--platform expects one of: chip8, schip, xochip"),
+            },
+            "--quirks" => match args.next().as_deref().and_then(Quirks::from_name) {
+                Some(preset) => quirks = Some(preset),
+                None => usage_error("--quirks expects one of: vip, chip48, schip, xochip"),
+            },
+            "--load-addr" => match args.next().as_deref().and_then(parse_address) {
+                Some(addr) => load_addr = addr,
+                None => usage_error("--load-addr expects an address such as 0x200"),
+            },
+            "--eti660" => load_addr = ETI_660_START_ADDR,
+            "--seed" => match args.next().and_then(|a| a.parse().ok()) {
+                Some(n) => seed = n,
+                None => usage_error("--seed expects a number"),
+            },
+            "--press" => match args.next().as_deref().and_then(KeyPress::parse) {
+                Some(press) => presses.push(press),
+                None => usage_error("--press expects FRAME:KEY or FRAME:KEY:FRAMES"),
+            },
+            "--pbm" => match args.next() {
+                Some(path) => pbm_path = Some(path),
+                None => usage_error("--pbm expects a file path"),
+            },
+            "--ascii" => ascii = true,
+            "--gdb" => match args.next().and_then(|a| a.parse::<u16>().ok()) {
+                Some(port) => gdb_port = Some(port),
+                None => usage_error("--gdb expects a port number"),
+            },
+            _ if arg.starts_with('-') => {
+                usage_error(&format!("Unknown option {}\n\n{}", arg, USAGE))
+            }
+            _ if rom_file_path.is_some() => {
+                usage_error(&format!("Only one ROM can be run at a time\n\n{}", USAGE))
+            }
+            _ => rom_file_path = Some(arg),
+        }
+    }
+    let Some(rom_file_path) = rom_file_path else {
+        usage_error(USAGE);
+    };
+    let quirks = quirks.unwrap_or(platform.default_quirks());
+    let buffer = match fs::read(&rom_file_path) {
+        Ok(buffer) => buffer,
+        Err(err) => usage_error(&format!("Unable to read {}: {}", rom_file_path, err)),
+    };
+    let mut chip8 = Chip8::new(platform, quirks);
+    chip8.set_seed(seed);
+    if let Err(err) = chip8.load(&buffer, load_addr) {
+        usage_error(&format!("Unable to load {}: {}", rom_file_path, err));
+    }
+
+    let mut frame = 0;
+    let result = match gdb_port {
+        Some(port) => Ok(serve_gdb(&mut chip8, port, ticks_per_frame)),
+        None => run(
+            &mut chip8,
+            frames,
+            ticks_per_frame,
+            until_loop,
+            &presses,
+            &mut frame,
+        ),
+    };
+
+    if ascii {
+        print!("{}", screen_to_ascii(&chip8));
+    }
+    if let Some(path) = pbm_path {
+        if let Err(err) = fs::write(&path, screen_to_pbm(&chip8)) {
+            eprintln!("Unable to write {}: {}", path, err);
+            process::exit(EXIT_USAGE);
+        }
+    }
+    print!("{}", register_dump(&chip8, frame));
+
+    let code = match result {
+        Ok(Outcome::FramesDone) if until_loop => {
+            eprintln!("No self-jump reached after {} frames", frames);
+            EXIT_TIMEOUT
+        }
+        Ok(_) => EXIT_OK,
+        Err(err) => {
+            eprintln!("Emulation halted: {}", err);
+            EXIT_CPU_ERROR
+        }
+    };
+    process::exit(code);
+}
+
+fn run(
+    chip8: &mut Chip8,
+    frames: u32,
+    ticks_per_frame: usize,
+    until_loop: bool,
+    presses: &[KeyPress],
+    frame: &mut u32,
+) -> Result<Outcome, Chip8Error> {
+    while *frame < frames {
+        for key in 0..16 {
+            let down = presses.iter().any(|p| p.key == key && p.is_down(*frame));
+            chip8.keypress(key, down);
+        }
+        for _ in 0..ticks_per_frame {
+            if until_loop && is_self_jump(chip8) {
+                return Ok(Outcome::SelfJump);
+            }
+            chip8.tick()?;
+            if chip8.has_exited() {
+                return Ok(Outcome::Exited);
+            }
+        }
+        chip8.tick_timers();
+        *frame += 1;
+    }
+    Ok(Outcome::FramesDone)
+}
+
+fn serve_gdb(chip8: &mut Chip8, port: u16, ticks_per_frame: usize) -> Outcome {
+    let session = TcpListener::bind(("127.0.0.1", port)).and_then(|listener| {
+        eprintln!("Waiting for a debugger on 127.0.0.1:{}", port);
+        let mut stub = GdbStub::accept(&listener, ticks_per_frame)?;
+        stub.run(chip8)
+    });
+    match session {
+        Ok(session) => eprintln!("Debugger session ended: {:?}", session),
+        Err(err) => {
+            eprintln!("Debugger connection failed: {}", err);
+            process::exit(EXIT_USAGE);
+        }
+    }
+    Outcome::Debugged
+}
+
+// Test ROMs end by jumping to themselves forever once they are done drawing
+fn is_self_jump(chip8: &Chip8) -> bool {
+    let pc = chip8.pc();
+    let memory = chip8.memory();
+    let Some(bytes) = memory.get(pc as usize..pc as usize + 2) else {
+        return false;
+    };
+    let op = (bytes[0] as u16) << 8 | bytes[1] as u16;
+    matches!(Instruction::decode(op), Ok(Instruction::Jump { nnn }) if nnn == pc)
+}
+
+fn screen_to_ascii(chip8: &Chip8) -> String {
+    let (screen, width, _) = chip8.get_screen();
+    let mut out = String::new();
+    for row in screen.chunks(width) {
+        out.extend(row.iter().map(|pixel| if *pixel != 0 { '#' } else { '.' }));
+        out.push('\n');
+    }
+    out
+}
+
+// Plain (P1) PBM, where 1 is a lit pixel on any plane
+fn screen_to_pbm(chip8: &Chip8) -> String {
+    let (screen, width, height) = chip8.get_screen();
+    let mut out = format!("P1\n{} {}\n", width, height);
+    for row in screen.chunks(width) {
+        let line: Vec<&str> = row
+            .iter()
+            .map(|pixel| if *pixel != 0 { "1" } else { "0" })
+            .collect();
+        out.push_str(&line.join(" "));
+        out.push('\n');
+    }
+    out
+}
+
+fn register_dump(chip8: &Chip8, frame: u32) -> String {
+    let mut out = String::new();
+    let _ = writeln!(out, "frame: {}", frame);
+    let _ = writeln!(
+        out,
+        "pc: {:#06X}  I: {:#06X}  DT: {}  ST: {}",
+        chip8.pc(),
+        chip8.index_register(),
+        chip8.delay_timer(),
+        chip8.sound_timer()
+    );
+    for (x, value) in chip8.registers().iter().enumerate() {
+        let separator = if x % 8 == 7 { '\n' } else { ' ' };
+        let _ = write!(out, "V{:X}: {:#04X}{}", x, value, separator);
+    }
+    let stack: Vec<String> = chip8
+        .stack()
+        .iter()
+        .map(|addr| format!("{:#06X}", addr))
+        .collect();
+    let _ = writeln!(out, "stack: [{}]", stack.join(", "));
+    out
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}", message);
+    process::exit(EXIT_USAGE);
+}
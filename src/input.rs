@@ -0,0 +1,137 @@
+// Which hex keys the player is holding, from every keyboard key and
+// controller button bound to them. A hex key pressed by several of them
+// stays down until the last one is let go.
+use crate::chip8::Chip8;
+use crate::keymap::Keymap;
+#[cfg(feature = "sdl")]
+use sdl2::controller::GameController;
+#[cfg(feature = "sdl")]
+use sdl2::event::Event;
+#[cfg(feature = "sdl")]
+use sdl2::GameControllerSubsystem;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    Keyboard,
+    // A game controller, by the id SDL gives it while it's plugged in
+    Controller(u32),
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Input {
+    held: Vec<(Source, usize)>,
+}
+
+impl Input {
+    pub fn press(&mut self, source: Source, key: usize) {
+        self.held.push((source, key & 0xF));
+    }
+
+    pub fn release(&mut self, source: Source, key: usize) {
+        if let Some(idx) = self
+            .held
+            .iter()
+            .position(|held| *held == (source, key & 0xF))
+        {
+            self.held.remove(idx);
+        }
+    }
+
+    // A controller button, by its SDL name, going down or up through `buttons`
+    pub fn button(&mut self, controller: u32, name: &str, down: bool, buttons: &Keymap) {
+        let Some(key) = buttons.key(name) else {
+            return;
+        };
+        let source = Source::Controller(controller);
+        if down {
+            self.press(source, key);
+        } else {
+            self.release(source, key);
+        }
+    }
+
+    // Everything a controller was holding when it was unplugged
+    pub fn release_all(&mut self, source: Source) {
+        self.held.retain(|(held_by, _)| *held_by != source);
+    }
+
+    pub fn is_down(&self, key: usize) -> bool {
+        self.held.iter().any(|(_, held)| *held == key)
+    }
+
+    pub fn apply(&self, chip8: &mut Chip8) {
+        for key in 0..16 {
+            chip8.keypress(key, self.is_down(key));
+        }
+    }
+}
+
+// A controller being plugged in or out, for the frontend to tell the player
+#[cfg(feature = "sdl")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Hotplug {
+    Connected(String),
+    Disconnected(String),
+    // A controller SDL found but couldn't open, by device index
+    Unusable { index: u32, error: String },
+}
+
+// The game controllers that are plugged in and the buttons they press keys with
+#[cfg(feature = "sdl")]
+pub struct Controllers {
+    subsystem: GameControllerSubsystem,
+    buttons: Keymap,
+    open: Vec<GameController>,
+}
+
+#[cfg(feature = "sdl")]
+impl Controllers {
+    // Controllers plugged in at startup arrive as ControllerDeviceAdded events too
+    pub fn new(subsystem: GameControllerSubsystem, buttons: Keymap) -> Self {
+        Self {
+            subsystem,
+            buttons,
+            open: Vec::new(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.open.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.open.is_empty()
+    }
+
+    // Feed a controller event into `input`; anything else is left alone
+    pub fn handle(&mut self, event: &Event, input: &mut Input) -> Option<Hotplug> {
+        match *event {
+            // `which` is the device index here and the instance id afterwards
+            Event::ControllerDeviceAdded { which, .. } => match self.subsystem.open(which) {
+                Ok(controller) => {
+                    let name = controller.name();
+                    self.open.push(controller);
+                    Some(Hotplug::Connected(name))
+                }
+                Err(err) => Some(Hotplug::Unusable {
+                    index: which,
+                    error: err.to_string(),
+                }),
+            },
+            Event::ControllerDeviceRemoved { which, .. } => {
+                input.release_all(Source::Controller(which));
+                let idx = self.open.iter().position(|c| c.instance_id() == which)?;
+                Some(Hotplug::Disconnected(self.open.remove(idx).name()))
+            }
+            Event::ControllerButtonDown { which, button, .. } => {
+                input.button(which, &button.string(), true, &self.buttons);
+                None
+            }
+            Event::ControllerButtonUp { which, button, .. } => {
+                input.button(which, &button.string(), false, &self.buttons);
+                None
+            }
+            _ => None,
+        }
+    }
+}
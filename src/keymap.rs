@@ -0,0 +1,107 @@
+/*
+COSMAC VIP used the following layout, which was then re-used on the HP48 calculators,
+This is the standard keypad used in emulators.
+By default we map them with the left hand side of the keyboard.
+    1	2	3	C
+    4	5	6	D
+    7	8	9	E
+    A	0	B	F
+*/
+
+// The host keys of the keypad from key 0 to key F, written the way Octo does
+pub const DEFAULT_KEYMAP: &str = "x123qweasdzc4rfv";
+
+// Game controller buttons, by the names SDL's controller mappings use. The
+// D-pad is the 5/7/8/9 cross under WASD, A and B are E and Q beside it.
+pub const DEFAULT_BUTTONS: [(&str, usize); 10] = [
+    ("dpup", 0x5),
+    ("dpleft", 0x7),
+    ("dpdown", 0x8),
+    ("dpright", 0x9),
+    ("a", 0x6),
+    ("b", 0x4),
+    ("x", 0xA),
+    ("y", 0xB),
+    ("leftshoulder", 0x1),
+    ("rightshoulder", 0xC),
+];
+
+// Which host keys press which key of the hex keypad. Host keys are physical
+// keys named the way SDL names their scancodes ("X", "1", "Up", "Keypad 8"),
+// compared without case, so a keymap written for a QWERTY keyboard presses
+// the same positions on any layout. Several host keys can press one hex key.
+// Controller buttons are mapped the same way, by name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Keymap {
+    bindings: Vec<(String, usize)>,
+}
+
+impl Keymap {
+    // 16 characters, the host key for each hex key from 0 to F
+    pub fn parse(text: &str) -> Option<Self> {
+        let chars: Vec<char> = text.chars().map(|c| c.to_ascii_uppercase()).collect();
+        if chars.len() != 16 {
+            return None;
+        }
+        // A host key can only press one hex key
+        if (1..16).any(|idx| chars[..idx].contains(&chars[idx])) {
+            return None;
+        }
+        Some(Self {
+            bindings: chars
+                .into_iter()
+                .enumerate()
+                .map(|(key, c)| (c.to_string(), key))
+                .collect(),
+        })
+    }
+
+    // The controller buttons from DEFAULT_BUTTONS
+    pub fn buttons() -> Self {
+        Self {
+            bindings: DEFAULT_BUTTONS
+                .iter()
+                .map(|(name, key)| (name.to_string(), *key))
+                .collect(),
+        }
+    }
+
+    // A "host key = hex key" binding such as "Up = 2" or "Keypad 8=8"
+    pub fn parse_binding(text: &str) -> Option<(String, usize)> {
+        // The last '=' so the = key can be bound too
+        let (name, key) = text.rsplit_once('=')?;
+        let name = name.trim();
+        let key = key.trim();
+        if name.is_empty() || key.len() != 1 {
+            return None;
+        }
+        Some((name.to_string(), usize::from_str_radix(key, 16).ok()?))
+    }
+
+    // Make the host key called `name` press hex key `key`, instead of
+    // whatever it pressed before
+    pub fn bind(&mut self, name: &str, key: usize) {
+        self.bindings
+            .retain(|(bound, _)| !bound.eq_ignore_ascii_case(name));
+        self.bindings.push((name.to_string(), key & 0xF));
+    }
+
+    // The hex key pressed by the host key called `name`
+    pub fn key(&self, name: &str) -> Option<usize> {
+        self.bindings
+            .iter()
+            .find(|(bound, _)| bound.eq_ignore_ascii_case(name))
+            .map(|(_, key)| *key)
+    }
+
+    // Every bound host key, for checking the names against the host's
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.bindings.iter().map(|(name, _)| name.as_str())
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        Self::parse(DEFAULT_KEYMAP).unwrap()
+    }
+}
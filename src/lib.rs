@@ -0,0 +1,17 @@
+// The emulator core and the pieces of the frontends that don't depend on SDL,
+// so other tools can drive a Chip8 directly.
+pub mod asm;
+pub mod audio;
+pub mod chip8;
+pub mod clock;
+pub mod config;
+pub mod debugger;
+pub mod disasm;
+pub mod gdb;
+pub mod input;
+pub mod keymap;
+pub mod palette;
+pub mod phosphor;
+pub mod rewind;
+pub mod romdb;
+pub mod sha1;
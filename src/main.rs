@@ -1,52 +1,400 @@
-use crate::chip8::{Chip8, SCREEN_HEIGHT, SCREEN_WIDTH};
+use rust_chip8::asm;
+use rust_chip8::audio::{Buzzer, BuzzerSettings, Waveform};
+use rust_chip8::chip8::{
+    parse_address, Chip8, FileTracer, Platform, Quirks, StdoutTracer, Tracer, ETI_660_START_ADDR,
+    SCREEN_HEIGHT, SCREEN_WIDTH, START_ADDR,
+};
+use rust_chip8::clock::{Clock, DEFAULT_INSTRUCTIONS_PER_SECOND, TIMER_HZ};
+use rust_chip8::config::{self, Config, Settings, MAX_SCALE};
+use rust_chip8::debugger::Debugger;
+use rust_chip8::disasm::{self, Syntax};
+use rust_chip8::input::{Controllers, Hotplug, Input, Source};
+use rust_chip8::keymap::Keymap;
+use rust_chip8::palette::Palette;
+use rust_chip8::phosphor::{Phosphor, MAX_PERSISTENCE_MS};
+use rust_chip8::rewind::{Rewind, DEFAULT_REWIND_BUDGET};
+use rust_chip8::romdb::RomDatabase;
+use sdl2::audio::{AudioCallback, AudioSpecDesired};
+use sdl2::controller::Button;
 use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use sdl2::keyboard::{Keycode, Scancode};
 use sdl2::pixels::Color;
 use sdl2::rect::Rect;
 use std::env;
-use std::fs::File;
-use std::io::Read;
+use std::fs;
+use std::io::{self, Write};
+use std::path::PathBuf;
+use std::process;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Instant;
 
-mod chip8;
+// The original display is 64 x 32, scaled up by --scale or this.
+// SUPER-CHIP high resolution pixels are drawn at half this size.
+const DEFAULT_SCALE: u32 = 15;
+const WINDOW_TITLE: &str = "Rust Chip-8 Emulator";
+const USAGE: &str = "Usage: chip8 [options] path/to/rom
+       chip8 disasm [--octo] [--platform P] [--load-addr ADDR | --eti660] path/to/rom
+       chip8 asm [--platform P] path/to/source.8o [-o path/to/rom]
 
-// The original display is 64 x 32. Scale it according to our needs.
-const SCALE: u32 = 15;
-const WINDOW_WIDTH: u32 = (SCREEN_WIDTH as u32) * SCALE;
-const WINDOW_HEIGHT: u32 = (SCREEN_HEIGHT as u32) * SCALE;
-const TICKS_PER_FRAME: usize = 15;
+disasm takes the platform from the CHIP-8 database when the ROM is in it, and
+otherwise decodes every SUPER-CHIP and XO-CHIP instruction. asm accepts all of
+them unless --platform narrows it down, so disasm's output always assembles.
+
+Options:
+    --platform chip8|schip|xochip       Machine the ROM was written for
+    --quirks vip|chip48|schip|xochip    Override the platform's quirks
+    --ipf N                             Instructions per 60 Hz frame (default 15)
+    --ips N                             Instructions per second, for speeds --ipf can't express
+    --scale N                           Window size in multiples of 64 x 32 (default 15)
+    --fullscreen                        Fill the screen instead of opening a window
+    --palette NAME|COLOURS              mono, amber, green, lcd, octo, or #RRGGBB colours
+                                        separated by commas: background and foreground,
+                                        or the colour of each XO-CHIP plane combination
+    --phosphor MS                       Fade pixels out over MS milliseconds instead of at
+                                        once, to hide the flicker of XOR drawing (default 0)
+    --keymap KEYS                       The 16 host keys for hex keys 0 to F (default x123qweasdzc4rfv),
+                                        by their place on a US keyboard whatever the layout
+    --key NAME=HEX                      Make another host key press a hex key, e.g. Up=2 or
+                                        \"Keypad 8=8\"; can be given more than once
+    --button NAME=HEX                   Make a controller button press a hex key, e.g. dpup=2;
+                                        buttons are a, b, x, y, back, guide, start, leftstick,
+                                        rightstick, leftshoulder, rightshoulder and dpup, dpdown,
+                                        dpleft, dpright. The D-pad is 5/7/8/9 by default,
+                                        a, b, x, y are 6, 4, A, B and the shoulders 1 and C
+    --config FILE                       Read settings from FILE instead of the default config
+    --romdb FILE                        Recognise ROMs with this programs.json from the CHIP-8
+                                        database instead of the built in copy
+    --tone HZ                           Buzzer frequency (default 440)
+    --volume 0..1                       Buzzer volume (default 0.25)
+    --waveform square|triangle|sawtooth|sine
+    --trace stdout|FILE                 Log every executed instruction
+    --load-addr ADDR                    Load the ROM at ADDR instead of 0x200
+    --eti660                            Load the ROM at 0x600 like the ETI-660
+    --truncate                          Cut ROMs that do not fit in memory instead of failing
+    --seed N                            Seed the random number generator to replay a run exactly
+    --debug                             Start paused and take debugger commands on stdin
+
+--platform, --quirks, --ipf, --ips, --scale, --fullscreen, --palette, --phosphor,
+--keymap, --key, --button and --seed can also be set in a TOML config file, at
+$XDG_CONFIG_HOME/rust-chip8/config.toml by default, with --key written as
+keys = [\"Up = 2\", ...] and --button as buttons = [\"dpup = 2\", ...]. Settings
+at the top apply to every ROM, a [rom.SHA1] table overrides them for the ROM
+with that SHA-1; its keys and buttons are bound on top of the ones at the top.
+ROMs found in the CHIP-8 database get the settings it lists for them over the
+ones at the top. Options given here win over everything else.
+
+Hotkeys:
+    F5 / F9                             Save / load the current state slot
+    F6 / F7                             Select the previous / next state slot
+    Backspace                           Hold to rewind";
+
+// Save states are written next to the ROM as rom.ch8.state0 to rom.ch8.state9
+const STATE_SLOTS: u8 = 10;
+const AUDIO_SAMPLE_RATE: i32 = 44_100;
+// Small buffers keep the delay between the sound timer and the speaker short
+const AUDIO_BUFFER_SAMPLES: u16 = 512;
+
+// SDL pulls samples from this on its audio thread. The main loop updates
+// the buzzer once per frame while holding the device lock.
+struct Speaker(Buzzer);
+
+impl AudioCallback for Speaker {
+    type Channel = f32;
+
+    fn callback(&mut self, out: &mut [f32]) {
+        self.0.fill(out);
+    }
+}
 
 fn main() {
+    let mut args = env::args().skip(1).peekable();
+    // Tools that share the core but never open a window
+    match args.peek().map(String::as_str) {
+        Some("disasm") => {
+            args.next();
+            process::exit(disasm_command(args));
+        }
+        Some("asm") => {
+            args.next();
+            process::exit(asm_command(args));
+        }
+        _ => (),
+    }
+
     println!("Welcome to Chip8...");
 
-    // TODO: Take SCALE and TICKS_PER_FRAME as user input.
-    let args: Vec<String> = env::args().collect();
-    if args.len() < 1 {
-        eprintln!("Usage: chip8 path/to/rom");
-        return;
+    // Settings given here win over the config file's
+    let mut cli = Settings::default();
+    let mut config_path = None;
+    let mut buzzer_settings = BuzzerSettings::default();
+    let mut tracer: Option<Box<dyn Tracer>> = None;
+    let mut load_addr = None;
+    let mut truncate = false;
+    let mut debug = false;
+    let mut romdb_path = None;
+    let mut rom_file_path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return;
+            }
+            "--load-addr" => match args.next().as_deref().and_then(parse_address) {
+                Some(addr) => load_addr = Some(addr),
+                None => usage_error("--load-addr expects an address such as 0x200"),
+            },
+            "--eti660" => load_addr = Some(ETI_660_START_ADDR),
+            "--truncate" => truncate = true,
+            "--debug" => debug = true,
+            "--fullscreen" => cli.fullscreen = Some(true),
+            "--config" => match args.next() {
+                Some(path) => config_path = Some(PathBuf::from(path)),
+                None => usage_error("--config expects a file path"),
+            },
+            "--romdb" => match args.next() {
+                Some(path) => romdb_path = Some(PathBuf::from(path)),
+                None => usage_error("--romdb expects the path of a programs.json"),
+            },
+            "--seed" => match args.next().and_then(|a| a.parse().ok()) {
+                Some(n) => cli.seed = Some(n),
+                None => usage_error("--seed expects a number"),
+            },
+            "--scale" => match args.next().and_then(|a| a.parse().ok()) {
+                Some(scale @ 1..=MAX_SCALE) => cli.scale = Some(scale),
+                _ => usage_error("--scale expects a number from 1 to 100"),
+            },
+            "--ipf" => match args.next().and_then(|a| a.parse::<u64>().ok()) {
+                Some(ipf) if ipf > 0 => cli.instructions_per_second = Some(ipf * TIMER_HZ),
+                _ => usage_error("--ipf expects a number of instructions per frame"),
+            },
+            "--ips" => match args.next().and_then(|a| a.parse().ok()) {
+                Some(ips) if ips > 0 => cli.instructions_per_second = Some(ips),
+                _ => usage_error("--ips expects a number of instructions per second"),
+            },
+            "--palette" => match args.next().as_deref().and_then(Palette::parse) {
+                Some(palette) => cli.palette = Some(palette),
+                None => usage_error(
+                    "--palette expects mono, amber, green, lcd, octo or 2 or 4 #RRGGBB colours",
+                ),
+            },
+            "--phosphor" => match args.next().and_then(|a| a.parse().ok()) {
+                Some(ms @ 0..=MAX_PERSISTENCE_MS) => cli.phosphor = Some(ms),
+                _ => usage_error("--phosphor expects a number of milliseconds up to 10000"),
+            },
+            "--keymap" => match args.next().as_deref().and_then(Keymap::parse) {
+                Some(keymap) => cli.keymap = Some(keymap),
+                None => usage_error("--keymap expects 16 different keys, for hex keys 0 to F"),
+            },
+            "--key" => match args.next().as_deref().and_then(Keymap::parse_binding) {
+                Some(binding) => cli.keys.push(binding),
+                None => usage_error("--key expects a host key and a hex key, such as Up=2"),
+            },
+            "--button" => match args.next().as_deref().and_then(Keymap::parse_binding) {
+                Some(binding) => cli.buttons.push(binding),
+                None => usage_error(
+                    "--button expects a controller button and a hex key, such as dpup=2",
+                ),
+            },
+            "--trace" => match args.next().as_deref() {
+                Some("stdout") => tracer = Some(Box::new(StdoutTracer)),
+                Some(path) => match FileTracer::create(path) {
+                    Ok(file_tracer) => tracer = Some(Box::new(file_tracer)),
+                    Err(err) => {
+                        eprintln!("Unable to create trace file {}: {}", path, err);
+                        process::exit(1);
+                    }
+                },
+                None => usage_error("--trace expects stdout or a file path"),
+            },
+            "--tone" => match args.next().and_then(|a| a.parse().ok()) {
+                Some(frequency) => buzzer_settings.frequency = frequency,
+                None => usage_error("--tone expects a frequency in Hz"),
+            },
+            "--volume" => match args.next().and_then(|a| a.parse::<f32>().ok()) {
+                Some(volume) => buzzer_settings.volume = volume.clamp(0.0, 1.0),
+                None => usage_error("--volume expects a number between 0 and 1"),
+            },
+            "--waveform" => match args.next().as_deref().and_then(Waveform::from_name) {
+                Some(waveform) => buzzer_settings.waveform = waveform,
+                None => usage_error("--waveform expects one of: square, triangle, sawtooth, sine"),
+            },
+            "--platform" => match args.next().as_deref().and_then(Platform::from_name) {
+                Some(p) => cli.platform = Some(p),
+                None => usage_error("--platform expects one of: chip8, schip, xochip"),
+            },
+            "--quirks" => match args.next().as_deref().and_then(Quirks::from_name) {
+                Some(preset) => cli.quirks = Some(preset),
+                None => usage_error("--quirks expects one of: vip, chip48, schip, xochip"),
+            },
+            _ if arg.starts_with('-') => usage_error(&format!("Unknown option {}", arg)),
+            _ if rom_file_path.is_some() => usage_error("Only one ROM can be run at a time"),
+            _ => rom_file_path = Some(arg),
+        }
     }
-    let rom_file_path = &args[1];
-    let mut rom = File::open(rom_file_path).expect("Unable to open file");
-    let mut buffer = Vec::new();
-    rom.read_to_end(&mut buffer).unwrap();
+    let Some(rom_file_path) = rom_file_path else {
+        usage_error("No ROM given");
+    };
+    let mut buffer = match fs::read(&rom_file_path) {
+        Ok(buffer) => buffer,
+        Err(err) => {
+            eprintln!("Unable to read {}: {}", rom_file_path, err);
+            process::exit(1);
+        }
+    };
+
+    // A config file named on the command line has to exist, the default one doesn't
+    let config = match config_path.clone().or_else(config::default_path) {
+        Some(path) if config_path.is_some() || path.exists() => match Config::load(&path) {
+            Ok(config) => config,
+            Err(err) => {
+                eprintln!("Unable to read config {}: {}", path.display(), err);
+                process::exit(1);
+            }
+        },
+        _ => Config::default(),
+    };
+    let database = match &romdb_path {
+        Some(path) => match RomDatabase::load(path) {
+            Ok(database) => database,
+            Err(err) => {
+                eprintln!("Unable to read ROM database {}: {}", path.display(), err);
+                process::exit(1);
+            }
+        },
+        None => RomDatabase::embedded(),
+    };
+    for reason in database.skipped() {
+        eprintln!("Warning: skipped a ROM database entry: {}", reason);
+    }
+    let known = database.lookup(&buffer);
+    if let Some(info) = known {
+        println!("Recognised {}", info.title);
+    }
+    let mut known_settings = known.map(|info| info.settings.clone()).unwrap_or_default();
+    // What the user says about the ROM comes first, then the database, then the
+    // config file's defaults. The database's quirks only go with its own platform.
+    let user_settings = cli.or(config.rom_settings(&buffer));
+    if user_settings.platform.is_some() && user_settings.platform != known_settings.platform {
+        known_settings.quirks = None;
+    }
+    let settings = user_settings.or(known_settings).or(config.defaults.clone());
+    let load_addr = load_addr
+        .or(known.and_then(|info| info.start_address))
+        .unwrap_or(START_ADDR);
+    let platform = settings.platform.unwrap_or_default();
+    // Each platform comes with the quirks its games expect unless told otherwise
+    let quirks = settings.quirks.unwrap_or(platform.default_quirks());
+    let scale = settings.scale.unwrap_or(DEFAULT_SCALE);
+    let window_width = SCREEN_WIDTH as u32 * scale;
+    let window_height = SCREEN_HEIGHT as u32 * scale;
+    let palette = settings.palette.unwrap_or_default();
+    let mut phosphor = Phosphor::new(palette, settings.phosphor.unwrap_or(0));
+    let keymap = settings.keymap_with_keys();
+    for name in keymap.names() {
+        if Scancode::from_name(name).is_none() {
+            eprintln!("Warning: there is no key called {:?} to bind", name);
+        }
+    }
+    let buttons = settings.button_map();
+    for name in buttons.names() {
+        if Button::from_string(name).is_none() {
+            eprintln!(
+                "Warning: there is no controller button called {:?} to bind",
+                name
+            );
+        }
+    }
+    let mut input = Input::default();
 
     // Setup SDL2 (Taken from https://docs.rs/sdl2/latest/sdl2/#functions)
     let sdl_context = sdl2::init().unwrap();
     let video_subsystem = sdl_context.video().unwrap();
 
-    let window = video_subsystem
-        .window("Rust Chip-8 Emulator", WINDOW_WIDTH, WINDOW_HEIGHT)
-        .position_centered()
-        .build()
-        .unwrap();
+    let mut window_builder = video_subsystem.window(WINDOW_TITLE, window_width, window_height);
+    window_builder.position_centered();
+    if settings.fullscreen.unwrap_or(false) {
+        window_builder.fullscreen_desktop();
+    }
+    let window = window_builder.build().unwrap();
 
-    let mut canvas = window.into_canvas().present_vsync().build().unwrap();
+    // Frames are paced by the emulation clock below, not the monitor's refresh
+    let mut canvas = window.into_canvas().build().unwrap();
+    // Draw at the window size; a fullscreen display gets it scaled up and letterboxed
+    canvas
+        .set_logical_size(window_width, window_height)
+        .unwrap();
     canvas.clear();
     canvas.present();
 
     let mut event_pump = sdl_context.event_pump().unwrap();
 
-    let mut chip8 = Chip8::new();
-    chip8.load(&buffer);
+    let mut controllers = Controllers::new(sdl_context.game_controller().unwrap(), buttons);
+
+    let audio_subsystem = sdl_context.audio().unwrap();
+    let desired_spec = AudioSpecDesired {
+        freq: Some(AUDIO_SAMPLE_RATE),
+        channels: Some(1),
+        samples: Some(AUDIO_BUFFER_SAMPLES),
+    };
+    let mut speaker = audio_subsystem
+        .open_playback(None, &desired_spec, |spec| {
+            Speaker(Buzzer::new(buzzer_settings, spec.freq))
+        })
+        .unwrap();
+    speaker.resume();
+
+    let mut chip8 = Chip8::new(platform, quirks);
+    let max_rom_size = chip8.max_rom_size(load_addr);
+    if truncate && buffer.len() > max_rom_size {
+        eprintln!(
+            "Warning: {} is {} bytes, only loading the first {}",
+            rom_file_path,
+            buffer.len(),
+            max_rom_size
+        );
+        buffer.truncate(max_rom_size);
+    }
+    if let Err(err) = chip8.load(&buffer, load_addr) {
+        eprintln!("Unable to load {}: {}", rom_file_path, err);
+        process::exit(1);
+    }
+    if let Some(tracer) = tracer {
+        chip8.set_tracer(tracer);
+    }
+    if let Some(seed) = settings.seed {
+        chip8.set_seed(seed);
+    }
+    // Set once the CPU reports an error; the last frame stays on screen
+    let mut halted = false;
+    let mut state_slot: u8 = 0;
+    let mut rewind = Rewind::new(DEFAULT_REWIND_BUDGET);
+    let mut rewinding = false;
+    // Commands are read on their own thread so the window keeps responding while paused
+    let mut debugger = debug.then(|| {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            for line in io::stdin().lines().map_while(Result::ok) {
+                if sender.send(line).is_err() {
+                    break;
+                }
+            }
+        });
+        (Debugger::new(), receiver)
+    });
+    if let Some((debugger, _)) = &debugger {
+        println!("{}", debugger.location(&chip8));
+        debugger_prompt();
+    }
+
+    // Emulation runs on wall time: the timers tick at exactly 60 Hz and the CPU at
+    // its own rate whatever the refresh rate of the monitor presenting the frames
+    let mut clock = Clock::new(
+        settings
+            .instructions_per_second
+            .unwrap_or(DEFAULT_INSTRUCTIONS_PER_SECOND),
+    );
+    let mut last_frame = Instant::now();
 
     'running: loop {
         for event in event_pump.poll_iter() {
@@ -56,77 +404,308 @@ fn main() {
                     keycode: Some(Keycode::Escape),
                     ..
                 } => break 'running,
-                // Keydown is registered as a keypress held
                 Event::KeyDown {
-                    keycode: Some(key), ..
+                    keycode: Some(Keycode::F5),
+                    repeat: false,
+                    ..
+                } => {
+                    let path = state_path(&rom_file_path, state_slot);
+                    match fs::write(&path, chip8.save_state()) {
+                        Ok(()) => println!("Saved state to {}", path),
+                        Err(err) => eprintln!("Unable to write {}: {}", path, err),
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::F9),
+                    repeat: false,
+                    ..
+                } => {
+                    let path = state_path(&rom_file_path, state_slot);
+                    let result = fs::read(&path)
+                        .map_err(|err| err.to_string())
+                        .and_then(|data| chip8.load_state(&data).map_err(|err| err.to_string()));
+                    match result {
+                        Ok(()) => {
+                            println!("Loaded state from {}", path);
+                            // A restored machine gets another chance to run
+                            halted = false;
+                            canvas.window_mut().set_title(WINDOW_TITLE).unwrap();
+                        }
+                        Err(err) => eprintln!("Unable to load {}: {}", path, err),
+                    }
+                }
+                Event::KeyDown {
+                    keycode: Some(key @ (Keycode::F6 | Keycode::F7)),
+                    repeat: false,
+                    ..
                 } => {
-                    if let Some(k) = key_to_button(key) {
-                        chip8.keypress(k, true);
+                    state_slot = if key == Keycode::F6 {
+                        (state_slot + STATE_SLOTS - 1) % STATE_SLOTS
+                    } else {
+                        (state_slot + 1) % STATE_SLOTS
+                    };
+                    println!("State slot {}", state_slot);
+                }
+                Event::KeyDown {
+                    keycode: Some(Keycode::Backspace),
+                    ..
+                } => rewinding = true,
+                Event::KeyUp {
+                    keycode: Some(Keycode::Backspace),
+                    ..
+                } => rewinding = false,
+                // Keys are mapped by where they are on the keyboard, not what they type
+                Event::KeyDown {
+                    scancode: Some(key),
+                    repeat: false,
+                    ..
+                } => {
+                    if let Some(k) = keymap.key(key.name()) {
+                        input.press(Source::Keyboard, k);
                     }
                 }
-                // Keyup is registered as keypress lifted
                 Event::KeyUp {
-                    keycode: Some(key), ..
+                    scancode: Some(key),
+                    ..
                 } => {
-                    if let Some(k) = key_to_button(key) {
-                        chip8.keypress(k, false);
+                    if let Some(k) = keymap.key(key.name()) {
+                        input.release(Source::Keyboard, k);
                     }
                 }
-                _ => (),
+                event => match controllers.handle(&event, &mut input) {
+                    Some(Hotplug::Connected(name)) => println!("Connected {}", name),
+                    Some(Hotplug::Disconnected(name)) => println!("Disconnected {}", name),
+                    Some(Hotplug::Unusable { index, error }) => {
+                        eprintln!("Unable to open controller {}: {}", index, error)
+                    }
+                    None => (),
+                },
+            }
+        }
+        input.apply(&mut chip8);
+        if let Some((debugger, commands)) = debugger.as_mut() {
+            while let Ok(line) = commands.try_recv() {
+                print!("{}", debugger.execute(&mut chip8, &line));
+                if debugger.quit_requested() {
+                    break 'running;
+                }
+                if debugger.is_paused() {
+                    debugger_prompt();
+                }
+            }
+        }
+        let now = Instant::now();
+        let frames = clock.advance(now - last_frame);
+        last_frame = now;
+        for _ in 0..frames {
+            let paused = debugger.as_ref().is_some_and(|(d, _)| d.is_paused());
+            if rewinding {
+                // Play the recorded frames back in reverse, one per frame
+                if let Some(state) = rewind.pop() {
+                    chip8
+                        .load_state(&state)
+                        .expect("rewind history holds valid states");
+                    if halted {
+                        halted = false;
+                        canvas.window_mut().set_title(WINDOW_TITLE).unwrap();
+                    }
+                }
+                speaker.lock().0.update(0, None);
+            } else if !halted && !paused {
+                let mut stopped = false;
+                for _ in 0..clock.frame_instructions() {
+                    if let Some((debugger, _)) = debugger.as_mut() {
+                        if let Some(stop) = debugger.before_tick(&chip8) {
+                            println!("{}\n{}", stop, debugger.location(&chip8));
+                            debugger_prompt();
+                            stopped = true;
+                            break;
+                        }
+                    }
+                    if let Err(err) = chip8.tick() {
+                        eprintln!("Emulation halted: {}", err);
+                        let title = format!("{} - halted: {}", WINDOW_TITLE, err);
+                        canvas.window_mut().set_title(&title).unwrap();
+                        halted = true;
+                        break;
+                    }
+                }
+                // The rest of the frame runs once the debugger continues, so
+                // the timers and the rewind history wait for it too
+                if stopped {
+                    speaker.lock().0.update(0, None);
+                    continue;
+                }
+                // Hand the sound timer over before this frame's tick so the
+                // buzzer plays for every frame the timer was running
+                speaker
+                    .lock()
+                    .0
+                    .update(chip8.sound_timer(), chip8.audio_pattern());
+                chip8.tick_timers();
+                rewind.push(chip8.save_state());
+            } else {
+                speaker.lock().0.update(0, None);
             }
         }
-        for _ in 0..TICKS_PER_FRAME {
-            chip8.tick();
+        // 00FD asks the interpreter to quit
+        if chip8.has_exited() {
+            break 'running;
         }
-        chip8.tick_timers();
-        // Draw the screen black
-        canvas.set_draw_color(Color::RGB(0, 0, 0));
+        // Fill the screen with the background colour
+        let [r, g, b] = palette.colors[0];
+        canvas.set_draw_color(Color::RGB(r, g, b));
         canvas.clear();
 
-        let screen = chip8.get_screen();
-        canvas.set_draw_color(Color::RGB(255, 255, 255));
+        let (screen, width, height) = chip8.get_screen();
+        if let Some(phosphor) = phosphor.as_mut() {
+            phosphor.update(screen, width, height, frames);
+        }
         for (i, pixel) in screen.iter().enumerate() {
-            if *pixel {
-                let x = (i % SCREEN_WIDTH) as u32;
-                let y = (i / SCREEN_WIDTH) as u32;
+            let color = match &phosphor {
+                Some(phosphor) => phosphor.color(i),
+                None => palette.colors[*pixel as usize],
+            };
+            if color != palette.colors[0] {
+                let x = (i % width) as u32;
+                let y = (i / width) as u32;
 
-                let rect = Rect::new((x * SCALE) as i32, (y * SCALE) as i32, SCALE, SCALE);
+                // Stretch the active resolution over the whole window
+                let left = x * window_width / width as u32;
+                let top = y * window_height / height as u32;
+                let right = (x + 1) * window_width / width as u32;
+                let bottom = (y + 1) * window_height / height as u32;
+                let rect = Rect::new(left as i32, top as i32, right - left, bottom - top);
+                let [r, g, b] = color;
+                canvas.set_draw_color(Color::RGB(r, g, b));
                 canvas.fill_rect(rect).unwrap();
             }
         }
         canvas.present();
+        // Sleep until the next 60 Hz frame is due instead of spinning
+        let wait = clock
+            .until_next_frame()
+            .saturating_sub(last_frame.elapsed());
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+    }
+}
+
+fn debugger_prompt() {
+    print!("(chip8) ");
+    let _ = io::stdout().flush();
+}
+
+// chip8 disasm: prints the ROM as classic mnemonics or Octo source
+fn disasm_command(mut args: impl Iterator<Item = String>) -> i32 {
+    let mut syntax = Syntax::Classic;
+    let mut platform = None;
+    let mut load_addr = START_ADDR;
+    let mut rom_file_path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--octo" => syntax = Syntax::Octo,
+            "--platform" => match args.next().as_deref().and_then(Platform::from_name) {
+                Some(p) => platform = Some(p),
+                None => {
+                    eprintln!("--platform expects one of: chip8, schip, xochip");
+                    return 2;
+                }
+            },
+            "--load-addr" => match args.next().as_deref().and_then(parse_address) {
+                Some(addr) => load_addr = addr,
+                None => {
+                    eprintln!("--load-addr expects an address such as 0x200");
+                    return 2;
+                }
+            },
+            "--eti660" => load_addr = ETI_660_START_ADDR,
+            _ if arg.starts_with('-') || rom_file_path.is_some() => {
+                eprintln!("Unexpected argument {}\n\n{}", arg, USAGE);
+                return 2;
+            }
+            _ => rom_file_path = Some(arg),
+        }
+    }
+    let Some(rom_file_path) = rom_file_path else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    match fs::read(&rom_file_path) {
+        Ok(rom) => {
+            // Without a platform to go on, decode everything rather than
+            // leave instructions from the extensions as data
+            let platform = platform
+                .or_else(|| {
+                    let database = RomDatabase::embedded();
+                    database.lookup(&rom)?.settings.platform
+                })
+                .unwrap_or(Platform::XoChip);
+            print!("{}", disasm::disassemble(&rom, load_addr, platform, syntax));
+            0
+        }
+        Err(err) => {
+            eprintln!("Unable to read {}: {}", rom_file_path, err);
+            1
+        }
     }
 }
 
-fn key_to_button(key: Keycode) -> Option<usize> {
-    /*
-    COSMAC VIP used the following layout, which was then re-used on the HP48 calculators,
-    This is the standard keypad used in emulators.
-    We map them with the left hand side of the keyboard.
-        1	2	3	C
-        4	5	6	D
-        7	8	9	E
-        A	0	B	F
-    */
-
-    // TODO: Change it so its not dependent on English Keyboard
-    match key {
-        Keycode::Num1 => Some(0x1),
-        Keycode::Num2 => Some(0x2),
-        Keycode::Num3 => Some(0x3),
-        Keycode::Num4 => Some(0xC),
-        Keycode::Q => Some(0x4),
-        Keycode::W => Some(0x5),
-        Keycode::E => Some(0x6),
-        Keycode::R => Some(0xD),
-        Keycode::A => Some(0x7),
-        Keycode::S => Some(0x8),
-        Keycode::D => Some(0x9),
-        Keycode::F => Some(0xE),
-        Keycode::Z => Some(0xA),
-        Keycode::X => Some(0x0),
-        Keycode::C => Some(0xB),
-        Keycode::V => Some(0xF),
-        _ => None,
+// chip8 asm: assembles Octo source into a ROM, next to the source unless -o is given
+fn asm_command(mut args: impl Iterator<Item = String>) -> i32 {
+    // Everything is allowed unless a platform is named to check against
+    let mut platform = Platform::XoChip;
+    let mut output = None;
+    let mut source_path = None;
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--platform" => match args.next().as_deref().and_then(Platform::from_name) {
+                Some(p) => platform = p,
+                None => {
+                    eprintln!("--platform expects one of: chip8, schip, xochip");
+                    return 2;
+                }
+            },
+            "-o" => match args.next() {
+                Some(path) => output = Some(PathBuf::from(path)),
+                None => {
+                    eprintln!("-o expects a file path");
+                    return 2;
+                }
+            },
+            _ if arg.starts_with('-') || source_path.is_some() => {
+                eprintln!("Unexpected argument {}\n\n{}", arg, USAGE);
+                return 2;
+            }
+            _ => source_path = Some(PathBuf::from(arg)),
+        }
     }
+    let Some(source_path) = source_path else {
+        eprintln!("{}", USAGE);
+        return 2;
+    };
+    let output = output.unwrap_or_else(|| source_path.with_extension("ch8"));
+    let rom = match asm::assemble_file(&source_path, platform) {
+        Ok(rom) => rom,
+        Err(err) => {
+            eprintln!("{}", err);
+            return 1;
+        }
+    };
+    if let Err(err) = fs::write(&output, &rom) {
+        eprintln!("Unable to write {}: {}", output.display(), err);
+        return 1;
+    }
+    println!("Wrote {} bytes to {}", rom.len(), output.display());
+    0
+}
+
+fn usage_error(message: &str) -> ! {
+    eprintln!("{}\n\n{}", message, USAGE);
+    process::exit(2);
+}
+
+fn state_path(rom_file_path: &str, slot: u8) -> String {
+    format!("{}.state{}", rom_file_path, slot)
 }
@@ -0,0 +1,74 @@
+// Colours a frontend paints the display with. A pixel's colour is picked by
+// the XO-CHIP planes it is lit on: none, the first, the second, or both.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Palette {
+    pub colors: [[u8; 3]; 4],
+}
+
+impl Palette {
+    // Built in palettes, see `parse` for custom ones
+    pub fn from_name(name: &str) -> Option<Self> {
+        let colors = match name.to_ascii_lowercase().as_str() {
+            "mono" | "default" => Self::default().colors,
+            "amber" => from_two([0, 0, 0], [0xFF, 0xB0, 0x00]),
+            "green" => from_two([0, 0, 0], [0x33, 0xFF, 0x33]),
+            "lcd" => from_two([0x9B, 0xBC, 0x0F], [0x0F, 0x38, 0x0F]),
+            // Octo's default colours
+            "octo" => [
+                [0x99, 0x66, 0x00],
+                [0xFF, 0xCC, 0x00],
+                [0xFF, 0x66, 0x00],
+                [0x66, 0x22, 0x00],
+            ],
+            _ => return None,
+        };
+        Some(Self { colors })
+    }
+
+    // A palette name, or comma separated #RRGGBB colours: background and
+    // foreground, or all four plane colours
+    pub fn parse(text: &str) -> Option<Self> {
+        if let Some(palette) = Self::from_name(text) {
+            return Some(palette);
+        }
+        let colors: Vec<[u8; 3]> = text
+            .split(',')
+            .map(|color| parse_color(color.trim()))
+            .collect::<Option<_>>()?;
+        let colors = match colors[..] {
+            [background, foreground] => from_two(background, foreground),
+            [a, b, c, d] => [a, b, c, d],
+            _ => return None,
+        };
+        Some(Self { colors })
+    }
+}
+
+impl Default for Palette {
+    fn default() -> Self {
+        Self {
+            colors: from_two([0, 0, 0], [255, 255, 255]),
+        }
+    }
+}
+
+// The second plane and the overlap of both get shades between the two colours
+fn from_two(background: [u8; 3], foreground: [u8; 3]) -> [[u8; 3]; 4] {
+    let mix = |weight: u16| {
+        let mut color = [0; 3];
+        for (channel, (bg, fg)) in color.iter_mut().zip(background.iter().zip(foreground)) {
+            *channel = ((*bg as u16 * (3 - weight) + fg as u16 * weight) / 3) as u8;
+        }
+        color
+    };
+    [background, foreground, mix(2), mix(1)]
+}
+
+fn parse_color(text: &str) -> Option<[u8; 3]> {
+    let hex = text.strip_prefix('#').unwrap_or(text);
+    if hex.len() != 6 || !hex.is_ascii() {
+        return None;
+    }
+    let channel = |idx: usize| u8::from_str_radix(&hex[idx..idx + 2], 16).ok();
+    Some([channel(0)?, channel(2)?, channel(4)?])
+}
@@ -0,0 +1,70 @@
+// Phosphor persistence for the display. Games erase a sprite by drawing it
+// again with XOR and then draw it somewhere else, so moving sprites are
+// missing from every other frame and flicker. Like the phosphor of a CRT,
+// pixels here light up at once but fade out over a few frames, which bridges
+// those gaps.
+use crate::clock::TIMER_HZ;
+use crate::palette::Palette;
+
+// Longest persistence, a fade of 10 seconds
+pub const MAX_PERSISTENCE_MS: u32 = 10_000;
+
+// How far a pixel has faded once the persistence time has passed
+const FADED: f32 = 0.05;
+
+#[derive(Debug, Clone)]
+pub struct Phosphor {
+    palette: Palette,
+    // How much of the way to its new colour a fading pixel is left with
+    // after a 60 Hz frame
+    decay: f32,
+    width: usize,
+    height: usize,
+    pixels: Vec<[f32; 3]>,
+}
+
+impl Phosphor {
+    // Unlit pixels take `persistence_ms` to fade to the background; None for 0
+    pub fn new(palette: Palette, persistence_ms: u32) -> Option<Self> {
+        if persistence_ms == 0 {
+            return None;
+        }
+        let frames = persistence_ms as f32 * TIMER_HZ as f32 / 1000.0;
+        Some(Self {
+            palette,
+            decay: FADED.powf(1.0 / frames.max(1.0)),
+            width: 0,
+            height: 0,
+            pixels: Vec::new(),
+        })
+    }
+
+    // Move every pixel `frames` 60 Hz frames on towards its colour in
+    // `screen`, as returned by Chip8::get_screen
+    pub fn update(&mut self, screen: &[u8], width: usize, height: usize, frames: u64) {
+        let target = |pixel: u8| self.palette.colors[pixel as usize & 3].map(|c| c as f32);
+        // A new resolution starts from a clean picture
+        if (width, height) != (self.width, self.height) {
+            self.width = width;
+            self.height = height;
+            self.pixels = screen.iter().map(|pixel| target(*pixel)).collect();
+            return;
+        }
+        let keep = self.decay.powi(frames.min(i32::MAX as u64) as i32);
+        for (shown, pixel) in self.pixels.iter_mut().zip(screen) {
+            let target = target(*pixel);
+            if *pixel != 0 {
+                *shown = target;
+            } else {
+                for (channel, target) in shown.iter_mut().zip(target) {
+                    *channel = target + (*channel - target) * keep;
+                }
+            }
+        }
+    }
+
+    // The colour of pixel `idx` of the last update, counted row by row
+    pub fn color(&self, idx: usize) -> [u8; 3] {
+        self.pixels[idx].map(|channel| channel.round() as u8)
+    }
+}
@@ -0,0 +1,149 @@
+use std::collections::VecDeque;
+
+// Enough for several minutes of a typical CHIP-8 game at 60 frames per second
+pub const DEFAULT_REWIND_BUDGET: usize = 4 * 1024 * 1024;
+
+// History of save states, newest last. Only the newest state is kept whole;
+// every older frame is stored as the difference that turns the frame after it
+// back into it. Consecutive frames mostly differ in a few registers and pixels,
+// so the differences are tiny once the unchanged runs are compressed away.
+#[derive(Debug)]
+pub struct Rewind {
+    budget: usize,
+    used: usize,
+    current: Option<Vec<u8>>,
+    deltas: VecDeque<Vec<u8>>,
+}
+
+impl Rewind {
+    // `budget` caps the bytes spent on deltas; the oldest frames are dropped first
+    pub fn new(budget: usize) -> Self {
+        Self {
+            budget,
+            used: 0,
+            current: None,
+            deltas: VecDeque::new(),
+        }
+    }
+
+    // Record the state of the frame that was just emulated
+    pub fn push(&mut self, state: Vec<u8>) {
+        match self.current.take() {
+            // Save states of the same machine always have the same length
+            Some(previous) if previous.len() == state.len() => {
+                let delta = encode_delta(&state, &previous);
+                self.used += delta.len();
+                self.deltas.push_back(delta);
+                while self.used > self.budget {
+                    match self.deltas.pop_front() {
+                        Some(oldest) => self.used -= oldest.len(),
+                        None => break,
+                    }
+                }
+            }
+            _ => self.clear(),
+        }
+        self.current = Some(state);
+    }
+
+    // Step one frame back and return its state, or None once the history runs out
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let delta = self.deltas.pop_back()?;
+        self.used -= delta.len();
+        let current = self.current.as_mut()?;
+        apply_delta(current, &delta);
+        Some(current.clone())
+    }
+
+    // Number of frames `pop` can still step back
+    pub fn len(&self) -> usize {
+        self.deltas.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.deltas.is_empty()
+    }
+
+    // Bytes spent on deltas, never more than the budget
+    pub fn used(&self) -> usize {
+        self.used
+    }
+
+    pub fn clear(&mut self) {
+        self.current = None;
+        self.deltas.clear();
+        self.used = 0;
+    }
+}
+
+// A delta is the XOR of two states, stored as pairs of a run of unchanged bytes
+// followed by a run of changed ones:
+//     unchanged count (varint), changed count (varint), changed bytes...
+// `to` must be as long as `from`; applying the delta to `to` gives `from`.
+pub fn encode_delta(from: &[u8], to: &[u8]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos < from.len() {
+        let start = pos;
+        while pos < from.len() && from[pos] == to[pos] {
+            pos += 1;
+        }
+        if pos == from.len() {
+            break;
+        }
+        let unchanged = pos - start;
+        let changed_start = pos;
+        while pos < from.len() && from[pos] != to[pos] {
+            pos += 1;
+        }
+        write_varint(&mut out, unchanged);
+        write_varint(&mut out, pos - changed_start);
+        out.extend(
+            from[changed_start..pos]
+                .iter()
+                .zip(&to[changed_start..pos])
+                .map(|(a, b)| a ^ b),
+        );
+    }
+    out
+}
+
+pub fn apply_delta(state: &mut [u8], delta: &[u8]) {
+    let mut pos = 0;
+    let mut cursor = 0;
+    while cursor < delta.len() {
+        pos += read_varint(delta, &mut cursor);
+        let changed = read_varint(delta, &mut cursor);
+        for (byte, diff) in state[pos..pos + changed]
+            .iter_mut()
+            .zip(&delta[cursor..cursor + changed])
+        {
+            *byte ^= diff;
+        }
+        pos += changed;
+        cursor += changed;
+    }
+}
+
+// Seven bits per byte, the high bit marks that more bytes follow
+fn write_varint(out: &mut Vec<u8>, mut value: usize) {
+    while value >= 0x80 {
+        out.push((value as u8 & 0x7F) | 0x80);
+        value >>= 7;
+    }
+    out.push(value as u8);
+}
+
+fn read_varint(data: &[u8], cursor: &mut usize) -> usize {
+    let mut value = 0;
+    let mut shift = 0;
+    loop {
+        let byte = data[*cursor];
+        *cursor += 1;
+        value |= ((byte & 0x7F) as usize) << shift;
+        if byte & 0x80 == 0 {
+            return value;
+        }
+        shift += 7;
+    }
+}
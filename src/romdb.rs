@@ -0,0 +1,243 @@
+// Lookup of known ROMs in the community CHIP-8 database
+// (https://github.com/chip-8/chip-8-database), which records the platform,
+// quirks, speed, colours and controls each game needs by the SHA-1 of the ROM.
+//
+// A copy of its programs.json is built in; a newer one can be loaded at run time.
+// Entries this emulator can't make sense of are skipped rather than failing the
+// whole database, since it is maintained elsewhere and keeps growing.
+use crate::chip8::{Platform, Quirks};
+use crate::config::Settings;
+use crate::palette::Palette;
+use crate::sha1::sha1_hex;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const EMBEDDED_PROGRAMS: &str = include_str!("../data/chip-8-database/programs.json");
+
+// Host keys and controller buttons bound to the database's directional and
+// action buttons
+const DIRECTION_KEYS: [(&str, &str, &str); 6] = [
+    ("up", "Up", "dpup"),
+    ("down", "Down", "dpdown"),
+    ("left", "Left", "dpleft"),
+    ("right", "Right", "dpright"),
+    ("a", "Space", "a"),
+    ("b", "Return", "b"),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RomInfo {
+    pub title: String,
+    // What the database says this ROM needs; anything it leaves out is None
+    pub settings: Settings,
+    pub start_address: Option<u16>,
+}
+
+#[derive(Debug)]
+pub enum RomDbError {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for RomDbError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RomDbError::Io(err) => write!(f, "{}", err),
+            RomDbError::Json(err) => write!(f, "{}", err),
+            RomDbError::Invalid(message) => write!(f, "{}", message),
+        }
+    }
+}
+
+impl std::error::Error for RomDbError {}
+
+#[derive(Debug, Clone, Default)]
+pub struct RomDatabase {
+    // Keyed by the lowercase hex SHA-1 of the ROM
+    roms: HashMap<String, RomInfo>,
+    // Why each skipped entry was left out
+    skipped: Vec<String>,
+}
+
+impl RomDatabase {
+    // The built in copy, empty if it doesn't even parse as a list of programs
+    pub fn embedded() -> Self {
+        Self::parse(EMBEDDED_PROGRAMS).unwrap_or_default()
+    }
+
+    // A programs.json from the database
+    pub fn load(path: &Path) -> Result<Self, RomDbError> {
+        let text = fs::read_to_string(path).map_err(RomDbError::Io)?;
+        Self::parse(&text)
+    }
+
+    pub fn parse(text: &str) -> Result<Self, RomDbError> {
+        let json: Value = serde_json::from_str(text).map_err(RomDbError::Json)?;
+        let programs = json
+            .as_array()
+            .ok_or_else(|| invalid("expected a list of programs"))?;
+        let mut roms = HashMap::new();
+        let mut skipped = Vec::new();
+        for (idx, program) in programs.iter().enumerate() {
+            let Some(title) = program.get("title").and_then(Value::as_str) else {
+                skipped.push(format!("program {} has no title", idx));
+                continue;
+            };
+            let Some(program_roms) = program.get("roms").and_then(Value::as_object) else {
+                continue;
+            };
+            for (hash, rom) in program_roms {
+                match rom_info(title, rom) {
+                    Ok(info) => {
+                        roms.insert(hash.to_ascii_lowercase(), info);
+                    }
+                    Err(message) => skipped.push(format!("{} ({}): {}", title, hash, message)),
+                }
+            }
+        }
+        Ok(Self { roms, skipped })
+    }
+
+    pub fn lookup(&self, rom: &[u8]) -> Option<&RomInfo> {
+        self.roms.get(&sha1_hex(rom))
+    }
+
+    pub fn skipped(&self) -> &[String] {
+        &self.skipped
+    }
+
+    pub fn len(&self) -> usize {
+        self.roms.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.roms.is_empty()
+    }
+}
+
+fn invalid(message: &str) -> RomDbError {
+    RomDbError::Invalid(message.to_string())
+}
+
+fn rom_info(title: &str, rom: &Value) -> Result<RomInfo, String> {
+    let mut settings = Settings::default();
+
+    // Platforms are listed best first; use the first one we can run
+    let platforms = rom
+        .get("platforms")
+        .and_then(Value::as_array)
+        .map_or(&[][..], Vec::as_slice);
+    let chosen = platforms
+        .iter()
+        .filter_map(Value::as_str)
+        .find_map(|name| Some((name, platform(name)?)));
+    if let Some((name, (platform, mut quirks))) = chosen {
+        if let Some(overrides) = rom
+            .get("quirkyPlatforms")
+            .and_then(|quirky| quirky.get(name))
+            .and_then(Value::as_object)
+        {
+            for (quirk, value) in overrides {
+                let value = value
+                    .as_bool()
+                    .ok_or_else(|| format!("quirk {} is not true or false", quirk))?;
+                apply_quirk(&mut quirks, quirk, value);
+            }
+        }
+        settings.platform = Some(platform);
+        settings.quirks = Some(quirks);
+    }
+
+    if let Some(tickrate) = rom.get("tickrate").and_then(Value::as_f64) {
+        if tickrate < 1.0 {
+            return Err(format!("tickrate {} is too slow", tickrate));
+        }
+        settings.instructions_per_second = Some(tickrate as u64 * 60);
+    }
+
+    if let Some(pixels) = rom
+        .get("colors")
+        .and_then(|colors| colors.get("pixels"))
+        .and_then(Value::as_array)
+    {
+        let colors: Vec<&str> = pixels.iter().filter_map(Value::as_str).collect();
+        // Palettes for more planes than XO-CHIP's two are left to the default
+        if colors.len() == 2 || colors.len() == 4 {
+            settings.palette = Some(
+                Palette::parse(&colors.join(","))
+                    .ok_or_else(|| format!("bad colours {:?}", colors))?,
+            );
+        }
+    }
+
+    if let Some(keys) = rom.get("keys") {
+        for (button, host, pad) in DIRECTION_KEYS {
+            if let Some(key) = keys.get(button).and_then(Value::as_f64) {
+                if !(0.0..16.0).contains(&key) {
+                    return Err(format!("key {} is not a hex key", key));
+                }
+                settings.keys.push((host.to_string(), key as usize));
+                settings.buttons.push((pad.to_string(), key as usize));
+            }
+        }
+    }
+
+    let start_address = match rom.get("startAddress").and_then(Value::as_f64) {
+        Some(addr) if (0.0..65536.0).contains(&addr) => Some(addr as u16),
+        Some(addr) => return Err(format!("start address {} is out of range", addr)),
+        None => None,
+    };
+
+    Ok(RomInfo {
+        title: title.to_string(),
+        settings,
+        start_address,
+    })
+}
+
+// The database's platform ids and the quirks it gives each one in platforms.json
+fn platform(name: &str) -> Option<(Platform, Quirks)> {
+    let platform = match name {
+        "originalChip8" | "hybridVIP" => (Platform::Chip8, Quirks::cosmac_vip()),
+        "modernChip8" => (
+            Platform::Chip8,
+            Quirks {
+                shift_uses_vy: true,
+                jump_uses_vx: false,
+                memory_increments_index: true,
+                memory_increment_by_x: false,
+                vf_reset: false,
+                clip_sprites: true,
+                display_wait: false,
+            },
+        ),
+        "chip48" => (Platform::Chip8, Quirks::chip48()),
+        "superchip1" | "superchip" => (Platform::SuperChip, Quirks::superchip()),
+        "xochip" => (Platform::XoChip, Quirks::xo_chip()),
+        _ => return None,
+    };
+    Some(platform)
+}
+
+// The database's quirk names, which say what the interpreter does rather than
+// what it doesn't, onto ours. Quirks we don't emulate are ignored.
+fn apply_quirk(quirks: &mut Quirks, name: &str, value: bool) {
+    match name {
+        "shift" => quirks.shift_uses_vy = !value,
+        "memoryLeaveIUnchanged" => quirks.memory_increments_index = !value,
+        "memoryIncrementByX" => {
+            quirks.memory_increment_by_x = value;
+            quirks.memory_increments_index |= value;
+        }
+        "wrap" => quirks.clip_sprites = !value,
+        "jump" => quirks.jump_uses_vx = value,
+        "vblank" => quirks.display_wait = value,
+        "logic" => quirks.vf_reset = value,
+        _ => {}
+    }
+}
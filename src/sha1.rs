@@ -0,0 +1,17 @@
+// SHA-1, used to recognise ROMs whatever their file is called. It's long
+// broken as a cryptographic hash but it's what ROM databases key games by.
+use sha1::{Digest, Sha1};
+use std::fmt::Write as _;
+
+pub fn sha1(data: &[u8]) -> [u8; 20] {
+    Sha1::digest(data).into()
+}
+
+// The digest as 40 lowercase hex digits
+pub fn sha1_hex(data: &[u8]) -> String {
+    let mut out = String::with_capacity(40);
+    for byte in sha1(data) {
+        let _ = write!(out, "{:02x}", byte);
+    }
+    out
+}
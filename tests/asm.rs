@@ -0,0 +1,125 @@
+use rust_chip8::asm::{assemble, assemble_file};
+use rust_chip8::chip8::{Platform, START_ADDR};
+use rust_chip8::disasm::{disassemble, Syntax};
+use std::fs;
+
+fn round_trip(rom: &[u8], origin: u16) -> Vec<u8> {
+    let source = disassemble(rom, origin, Platform::XoChip, Syntax::Octo);
+    assemble(&source, Platform::XoChip).unwrap_or_else(|err| panic!("{}\nin:\n{}", err, source))
+}
+
+#[test]
+fn every_opcode_round_trips() {
+    for word in 0..=u16::MAX {
+        let rom = word.to_be_bytes();
+        assert_eq!(round_trip(&rom, START_ADDR), rom, "opcode {:04X}", word);
+    }
+}
+
+#[test]
+fn long_index_round_trips() {
+    let rom = [0xF0, 0x00, 0x02, 0x06, 0x12, 0x04, 0xAA, 0x55];
+    assert_eq!(round_trip(&rom, START_ADDR), rom);
+}
+
+#[test]
+fn program_with_code_and_data_round_trips() {
+    let rom = assemble(
+        "
+        : main
+            clear
+            i := smiley
+            v0 := 0
+            v1 := 0
+        : loop
+            draw
+            v0 += 1
+            if v0 == 56 then v0 := 0
+            v2 := random 0x0f
+            if v2 -key then jump main
+            jump loop
+        : draw
+            sprite v0 v1 4
+            return
+        : smiley
+            0b00100100 0x00 0x81 0x7E
+        ",
+        Platform::Chip8,
+    )
+    .unwrap();
+    assert_eq!(round_trip(&rom, START_ADDR), rom);
+}
+
+#[test]
+fn origin_is_kept_with_org() {
+    let rom = [0x60, 0x01, 0x16, 0x02];
+    let source = disassemble(&rom, 0x600, Platform::Chip8, Syntax::Octo);
+    assert!(source.starts_with(":org 0x600"));
+    assert_eq!(assemble(&source, Platform::Chip8).unwrap(), rom);
+}
+
+#[test]
+fn labels_may_be_used_before_they_are_defined() {
+    let rom = assemble("jump end 0x00 : end i := long end", Platform::XoChip).unwrap();
+    assert_eq!(rom, [0x12, 0x03, 0x00, 0xF0, 0x00, 0x02, 0x03]);
+}
+
+#[test]
+fn constants_bytes_and_negative_numbers() {
+    let rom = assemble(
+        ":const SPEED 3\n v4 := SPEED v4 -= 1 :byte SPEED -1",
+        Platform::Chip8,
+    )
+    .unwrap();
+    assert_eq!(rom, [0x64, 0x03, 0x74, 0xFF, 0x03, 0xFF]);
+}
+
+#[test]
+fn includes_are_relative_to_the_including_file() {
+    let dir = std::env::temp_dir().join(format!("chip8-asm-include-{}", std::process::id()));
+    fs::create_dir_all(dir.join("lib")).unwrap();
+    fs::write(dir.join("main.8o"), ": main\n:include \"lib/sprites.8o\"\n").unwrap();
+    fs::write(dir.join("lib/sprites.8o"), ":include \"digits.8o\"\n0x01\n").unwrap();
+    fs::write(dir.join("lib/digits.8o"), "0xF0 0x90\n").unwrap();
+    let rom = assemble_file(&dir.join("main.8o"), Platform::Chip8);
+    fs::remove_dir_all(&dir).unwrap();
+    assert_eq!(rom.unwrap(), [0xF0, 0x90, 0x01]);
+}
+
+#[test]
+fn errors_point_at_the_offending_token() {
+    let err = assemble("clear\n  v0 := 0x100\n", Platform::Chip8).unwrap_err();
+    assert_eq!((err.line, err.col), (2, 9));
+    assert_eq!(err.to_string(), "<input>:2:9: 256 does not fit in a byte");
+
+    let err = assemble("jump nowhere", Platform::Chip8).unwrap_err();
+    assert_eq!((err.line, err.col), (1, 6));
+    assert!(err.message.contains("undefined label 'nowhere'"));
+
+    let err = assemble("\n\n\tv0 ** v1", Platform::Chip8).unwrap_err();
+    assert_eq!((err.line, err.col), (3, 5));
+
+    let err = assemble(": a\n: a", Platform::Chip8).unwrap_err();
+    assert_eq!((err.line, err.col), (2, 3));
+
+    let err = assemble("if v0 == 1\n", Platform::Chip8).unwrap_err();
+    assert!(err.message.starts_with("expected then"));
+}
+
+#[test]
+fn extensions_need_their_platform() {
+    assert!(assemble("hires", Platform::Chip8).is_err());
+    assert!(assemble("hires", Platform::SuperChip).is_ok());
+    assert!(assemble("plane 3", Platform::SuperChip).is_err());
+    assert!(assemble("plane 3", Platform::XoChip).is_ok());
+
+    // The disassembler leaves them as data, so its output assembles again
+    let source = disassemble(
+        &[0x00, 0xFF, 0x12, 0x00],
+        START_ADDR,
+        Platform::Chip8,
+        Syntax::Octo,
+    );
+    assert!(!source.contains("hires"), "{}", source);
+    assert!(assemble(&source, Platform::Chip8).is_ok(), "{}", source);
+}
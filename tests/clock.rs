@@ -0,0 +1,74 @@
+use rust_chip8::clock::{Clock, DEFAULT_INSTRUCTIONS_PER_SECOND};
+use std::time::Duration;
+
+// Frames due after calling advance() `calls` times, `interval` apart
+fn frames_after(clock: &mut Clock, interval: Duration, calls: u32) -> u64 {
+    (0..calls).map(|_| clock.advance(interval)).sum()
+}
+
+#[test]
+fn timers_run_at_60_hz_whatever_the_refresh_rate() {
+    for refresh_rate in [30, 60, 75, 144, 240] {
+        let mut clock = Clock::default();
+        let interval = Duration::from_secs(1) / refresh_rate;
+        let frames = frames_after(&mut clock, interval, refresh_rate * 10);
+        // Intervals are rounded down to whole nanoseconds, so a frame may be missing
+        assert!(
+            (599..=600).contains(&frames),
+            "{} Hz: {}",
+            refresh_rate,
+            frames
+        );
+    }
+}
+
+#[test]
+fn leftover_time_carries_over() {
+    let mut clock = Clock::default();
+    assert_eq!(clock.advance(Duration::from_millis(10)), 0);
+    assert_eq!(clock.advance(Duration::from_millis(10)), 1);
+    assert_eq!(clock.advance(Duration::from_millis(30)), 2);
+    assert_eq!(clock.advance(Duration::from_nanos(16_666_666)), 0);
+    assert_eq!(clock.advance(Duration::from_nanos(1)), 1);
+}
+
+#[test]
+fn next_frame_is_due_after_the_leftover_time() {
+    let mut clock = Clock::default();
+    assert_eq!(clock.until_next_frame(), Duration::from_nanos(16_666_667));
+    assert_eq!(clock.advance(Duration::from_millis(10)), 0);
+    assert_eq!(clock.until_next_frame(), Duration::from_nanos(6_666_667));
+
+    // Sleeping for exactly that long always makes the frame due
+    for _ in 0..100 {
+        let wait = clock.until_next_frame();
+        assert_eq!(clock.advance(wait), 1);
+    }
+}
+
+#[test]
+fn long_stalls_are_not_caught_up() {
+    let mut clock = Clock::default();
+    assert_eq!(clock.advance(Duration::from_secs(5)), 15);
+}
+
+#[test]
+fn instructions_average_out_to_the_rate() {
+    let mut clock = Clock::default();
+    assert_eq!(clock.frame_instructions(), 15);
+    assert_eq!(
+        clock.instructions_per_second(),
+        DEFAULT_INSTRUCTIONS_PER_SECOND
+    );
+
+    // 1000 per second is 16.67 per frame
+    let mut clock = Clock::new(1000);
+    let frames: Vec<usize> = (0..60).map(|_| clock.frame_instructions()).collect();
+    assert!(frames.iter().all(|n| (16..=17).contains(n)));
+    assert_eq!(frames.iter().sum::<usize>(), 1000);
+
+    // Rates below 60 run some frames without any instructions
+    let mut clock = Clock::new(20);
+    let frames: Vec<usize> = (0..6).map(|_| clock.frame_instructions()).collect();
+    assert_eq!(frames, [0, 0, 1, 0, 0, 1]);
+}
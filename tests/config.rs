@@ -0,0 +1,218 @@
+use rust_chip8::chip8::{Platform, Quirks};
+use rust_chip8::config::{Config, ConfigError, Settings};
+use rust_chip8::keymap::Keymap;
+use rust_chip8::palette::Palette;
+use rust_chip8::sha1::sha1_hex;
+
+const ROM: &[u8] = &[0x12, 0x00];
+
+fn syntax_error(text: &str) -> (usize, String) {
+    match Config::parse(text) {
+        Err(ConfigError::Syntax { line, message }) => (line, message),
+        other => panic!("expected a syntax error, got {:?}", other),
+    }
+}
+
+#[test]
+fn sha1_matches_known_digests() {
+    assert_eq!(sha1_hex(b""), "da39a3ee5e6b4b0d3255bfef95601890afd80709");
+    assert_eq!(sha1_hex(b"abc"), "a9993e364706816aba3e25717850c26c9cd0d89d");
+    // Two blocks once padded
+    assert_eq!(
+        sha1_hex(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq"),
+        "84983e441c3bd26ebaae4aa1f95129e5e54670f1"
+    );
+    assert_eq!(
+        sha1_hex(&[b'a'; 1000]),
+        "291e9a6c66994949b57ba5e650361e98fc36b1ba"
+    );
+}
+
+#[test]
+fn rom_sections_override_the_defaults() {
+    let text = format!(
+        r##"
+        # Global defaults
+        scale = 10
+        ipf = 20
+        palette = "amber"
+        fullscreen = false
+
+        [rom.{}]
+        name = "Jump # to self"
+        platform = "schip"   # SCHIP game
+        ipf = 30
+        seed = 0x2A
+        "##,
+        sha1_hex(ROM).to_uppercase()
+    );
+    let config = Config::parse(&text).unwrap();
+
+    let settings = config.settings_for(ROM);
+    assert_eq!(settings.scale, Some(10));
+    assert_eq!(settings.instructions_per_second, Some(30 * 60));
+    assert_eq!(settings.platform, Some(Platform::SuperChip));
+    assert_eq!(settings.palette, Palette::from_name("amber"));
+    assert_eq!(settings.seed, Some(42));
+    assert_eq!(settings.fullscreen, Some(false));
+
+    let other = config.settings_for(&[0x00, 0xE0]);
+    assert_eq!(other.instructions_per_second, Some(20 * 60));
+    assert_eq!(other.platform, None);
+}
+
+#[test]
+fn command_line_wins() {
+    let config =
+        Config::parse("quirks = \"vip\"\nscale = 4\nkeymap = \"0123456789abcdef\"").unwrap();
+    let cli = Settings {
+        quirks: Some(Quirks::chip48()),
+        ..Settings::default()
+    };
+    let settings = cli.or(config.settings_for(ROM));
+    assert_eq!(settings.quirks, Some(Quirks::chip48()));
+    assert_eq!(settings.scale, Some(4));
+    assert_eq!(settings.keymap.unwrap().key("A"), Some(0xA));
+}
+
+#[test]
+fn values() {
+    let config = Config::parse(
+        "palette = [\"#102030\", \"#405060\", \"#708090\", \"#A0B0C0\"]\nips = 1_000",
+    )
+    .unwrap();
+    assert_eq!(
+        config.defaults.palette.unwrap().colors,
+        [
+            [0x10, 0x20, 0x30],
+            [0x40, 0x50, 0x60],
+            [0x70, 0x80, 0x90],
+            [0xA0, 0xB0, 0xC0]
+        ]
+    );
+    assert_eq!(config.defaults.instructions_per_second, Some(1000));
+}
+
+#[test]
+fn errors_name_the_line() {
+    assert_eq!(syntax_error("scale = 1\nscal = 2").0, 2);
+    assert!(syntax_error("scale = 0").1.contains("between 1 and 100"));
+    assert!(syntax_error("scale = \"big\"")
+        .1
+        .contains("expected a number"));
+    assert!(syntax_error("platform = \"nes\"").1.contains("platform"));
+    assert_eq!(syntax_error("scale = 1\nseed = 1 2").0, 2);
+    assert!(syntax_error("name = \"x").1.contains("string"));
+    assert!(syntax_error("fullscreen").1.contains("expected `=`"));
+    assert!(syntax_error("[display]").1.contains("unknown table"));
+    assert!(syntax_error("[rom.1234]").1.contains("SHA-1"));
+    assert_eq!(
+        syntax_error("scale = 2\nscale = 3"),
+        (2, "duplicate key".to_string())
+    );
+
+    // Hashes are the same ROM whatever their case
+    let hash = sha1_hex(ROM);
+    let (line, message) = syntax_error(&format!("[rom.{}]\n\n[rom.{}]", hash, hash.to_uppercase()));
+    assert_eq!(line, 3);
+    assert!(message.contains("defined twice"));
+    let (line, _) = syntax_error(&format!("[rom.{0}]\nseed = 1\n[rom.{0}]", hash));
+    assert_eq!(line, 3);
+    assert_eq!(syntax_error(&format!("[rom.{}]\nscale = 0", hash)).0, 2);
+    // name is only allowed in ROM sections
+    assert!(syntax_error("name = \"x\"").1.contains("unknown setting"));
+}
+
+#[test]
+fn arrays_may_span_lines() {
+    let text = format!(
+        r##"
+        palette = [
+            "#000000",  # background
+            "#FFFFFF",
+        ]
+
+        [rom.{}]
+        buttons = [
+            "dpup = 2", "dpdown = 8",
+            "dpleft = 4",
+        ]
+        ips = 1000
+        "##,
+        sha1_hex(ROM)
+    );
+    let config = Config::parse(&text).unwrap();
+    assert_eq!(config.defaults.palette, Palette::parse("#000000,#FFFFFF"));
+    let settings = config.settings_for(ROM);
+    assert_eq!(settings.buttons.len(), 3);
+    assert_eq!(settings.button_map().key("dpleft"), Some(4));
+    assert_eq!(settings.instructions_per_second, Some(1000));
+}
+
+#[test]
+fn palettes() {
+    assert_eq!(Palette::parse("mono"), Some(Palette::default()));
+    let palette = Palette::parse("#000000, #FFFFFF").unwrap();
+    assert_eq!(palette.colors[2], [170, 170, 170]);
+    assert_eq!(palette.colors[3], [85, 85, 85]);
+    assert!(Palette::parse("#000000").is_none());
+    assert!(Palette::parse("#000000,#GGGGGG").is_none());
+}
+
+#[test]
+fn keymaps() {
+    let keymap = Keymap::default();
+    assert_eq!(keymap.key("X"), Some(0x0));
+    assert_eq!(keymap.key("1"), Some(0x1));
+    assert_eq!(keymap.key("v"), Some(0xF));
+    assert_eq!(keymap.key("P"), None);
+    assert_eq!(keymap.key("Left Shift"), None);
+    assert!(Keymap::parse("x123").is_none());
+    assert!(Keymap::parse("xx23qweasdzc4rfv").is_none());
+
+    assert_eq!(Keymap::parse_binding("Up=2"), Some(("Up".to_string(), 2)));
+    assert_eq!(
+        Keymap::parse_binding(" Keypad 8 = a "),
+        Some(("Keypad 8".to_string(), 0xA))
+    );
+    assert_eq!(Keymap::parse_binding("==F"), Some(("=".to_string(), 0xF)));
+    assert!(Keymap::parse_binding("Up").is_none());
+    assert!(Keymap::parse_binding("Up=10").is_none());
+    assert!(Keymap::parse_binding("=2").is_none());
+}
+
+#[test]
+fn keys_add_up_across_layers() {
+    let text = format!(
+        r#"
+        keymap = "0123456789abcdef"
+        keys = ["Keypad 1 = 1", "Up = 5"]
+
+        [rom.{}]
+        keys = ["Up = 2", "Down = 8", "Left = 4", "Right = 6"]
+        "#,
+        sha1_hex(ROM)
+    );
+    let config = Config::parse(&text).unwrap();
+    let cli = Settings {
+        keys: vec![("Right".to_string(), 9)],
+        ..Settings::default()
+    };
+    let keymap = cli.or(config.settings_for(ROM)).keymap_with_keys();
+    // Several host keys press 2
+    assert_eq!(keymap.key("2"), Some(2));
+    assert_eq!(keymap.key("up"), Some(2));
+    assert_eq!(keymap.key("Keypad 1"), Some(1));
+    assert_eq!(keymap.key("Down"), Some(8));
+    assert_eq!(keymap.key("Right"), Some(9));
+    // The keymap from the top replaces the default one
+    assert_eq!(keymap.key("X"), None);
+
+    let other = config.settings_for(&[0x00, 0xE0]).keymap_with_keys();
+    assert_eq!(other.key("Up"), Some(5));
+    assert_eq!(other.key("Down"), None);
+
+    assert!(syntax_error("keys = \"Up = 2\"").1.contains("array"));
+    assert_eq!(syntax_error("keys = [\n  \"Up = 2\",\n  3,\n]").0, 1);
+    assert!(syntax_error("keys = [\"Up\"]").1.contains("'Up'"));
+}
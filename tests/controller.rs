@@ -0,0 +1,88 @@
+// A virtual controller from SDL standing in for a real one, so this needs
+// SDL but no hardware
+#![cfg(feature = "sdl")]
+
+use rust_chip8::config::Config;
+use rust_chip8::input::{Controllers, Hotplug, Input};
+use rust_chip8::sha1::sha1_hex;
+use sdl2::sys;
+use sdl2::EventPump;
+
+// Buttons of the virtual controller, in the order of its mapping below
+const A: i32 = 0;
+const DPUP: i32 = 1;
+
+const ROM: &[u8] = &[0x12, 0x00];
+
+// Everything the controllers made of the pending events
+fn pump(events: &mut EventPump, controllers: &mut Controllers, input: &mut Input) -> Vec<Hotplug> {
+    let pending: Vec<_> = events.poll_iter().collect();
+    pending
+        .iter()
+        .filter_map(|event| controllers.handle(event, input))
+        .collect()
+}
+
+fn set_button(joystick: *mut sys::SDL_Joystick, button: i32, pressed: bool) {
+    let result = unsafe { sys::SDL_JoystickSetVirtualButton(joystick, button, pressed as u8) };
+    assert_eq!(result, 0, "{}", sdl2::get_error());
+}
+
+#[test]
+fn virtual_controller_presses_keys_and_unplugs() {
+    let sdl = sdl2::init().unwrap();
+    let joysticks = sdl.joystick().unwrap();
+    let subsystem = sdl.game_controller().unwrap();
+    let mut events = sdl.event_pump().unwrap();
+
+    // This ROM moves up with 2 instead of the default 5
+    let config = Config::parse(&format!(
+        "[rom.{}]\nbuttons = [\"dpup = 2\"]",
+        sha1_hex(ROM)
+    ))
+    .unwrap();
+    let mut controllers =
+        Controllers::new(subsystem.clone(), config.settings_for(ROM).button_map());
+    let mut input = Input::default();
+
+    let index = unsafe {
+        sys::SDL_JoystickAttachVirtual(
+            sys::SDL_JoystickType::SDL_JOYSTICK_TYPE_GAMECONTROLLER,
+            0,
+            2,
+            0,
+        )
+    };
+    assert!(index >= 0, "{}", sdl2::get_error());
+    let guid = joysticks.device_guid(index as u32).unwrap();
+    subsystem
+        .add_mapping(&format!("{},Virtual pad,a:b{},dpup:b{}", guid, A, DPUP))
+        .unwrap();
+    let joystick = unsafe { sys::SDL_JoystickOpen(index) };
+    assert!(!joystick.is_null(), "{}", sdl2::get_error());
+
+    // Plugged in while running
+    let plugged = pump(&mut events, &mut controllers, &mut input);
+    assert_eq!(plugged, [Hotplug::Connected("Virtual pad".to_string())]);
+    assert_eq!(controllers.len(), 1);
+
+    set_button(joystick, DPUP, true);
+    set_button(joystick, A, true);
+    pump(&mut events, &mut controllers, &mut input);
+    assert!(input.is_down(2));
+    assert!(!input.is_down(5));
+    assert!(input.is_down(6));
+
+    set_button(joystick, A, false);
+    pump(&mut events, &mut controllers, &mut input);
+    assert!(input.is_down(2));
+    assert!(!input.is_down(6));
+
+    // Unplugged with the D-pad still held
+    unsafe { sys::SDL_JoystickClose(joystick) };
+    assert_eq!(unsafe { sys::SDL_JoystickDetachVirtual(index) }, 0);
+    let plugged = pump(&mut events, &mut controllers, &mut input);
+    assert_eq!(plugged, [Hotplug::Disconnected("Virtual pad".to_string())]);
+    assert!(controllers.is_empty());
+    assert!(!input.is_down(2));
+}
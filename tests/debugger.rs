@@ -0,0 +1,121 @@
+use rust_chip8::chip8::{Chip8, Platform, Quirks, START_ADDR};
+use rust_chip8::debugger::{Debugger, Stop};
+
+// 0x200: V0 += 1, 0x202: V1 += 2, 0x204: draw, 0x206: jump 0x200
+const PROGRAM: [u8; 8] = [0x70, 0x01, 0x71, 0x02, 0xD0, 0x11, 0x12, 0x00];
+
+fn machine() -> Chip8 {
+    let mut chip8 = Chip8::new(Platform::Chip8, Quirks::chip48());
+    chip8.load(&PROGRAM, START_ADDR).unwrap();
+    chip8
+}
+
+// Tick until the debugger stops execution, returning why and how many instructions ran
+fn run(debugger: &mut Debugger, chip8: &mut Chip8) -> (Stop, usize) {
+    for executed in 0..1000 {
+        if let Some(stop) = debugger.before_tick(chip8) {
+            return (stop, executed);
+        }
+        chip8.tick().unwrap();
+    }
+    panic!("the debugger never stopped");
+}
+
+#[test]
+fn starts_paused_and_steps() {
+    let mut chip8 = machine();
+    let mut debugger = Debugger::new();
+    assert!(debugger.is_paused());
+    assert!(debugger.location(&chip8).contains("0x200"));
+
+    debugger.execute(&mut chip8, "step 3");
+    assert!(!debugger.is_paused());
+    assert_eq!(run(&mut debugger, &mut chip8), (Stop::Step, 3));
+    assert_eq!(chip8.pc(), 0x206);
+
+    // An empty line repeats the last command
+    debugger.execute(&mut chip8, "");
+    assert_eq!(run(&mut debugger, &mut chip8), (Stop::Step, 3));
+    assert_eq!(chip8.pc(), 0x204);
+}
+
+#[test]
+fn breakpoints_stop_before_the_instruction() {
+    let mut chip8 = machine();
+    let mut debugger = Debugger::new();
+    debugger.execute(&mut chip8, "break 204");
+    debugger.execute(&mut chip8, "continue");
+    assert_eq!(run(&mut debugger, &mut chip8), (Stop::Breakpoint(0x204), 2));
+
+    // Continuing leaves the breakpoint it is sitting on
+    debugger.execute(&mut chip8, "c");
+    assert_eq!(run(&mut debugger, &mut chip8), (Stop::Breakpoint(0x204), 4));
+    assert_eq!(chip8.registers()[0], 2);
+
+    debugger.execute(&mut chip8, "delete 204");
+    assert_eq!(debugger.execute(&mut chip8, "break"), "No breakpoints\n");
+}
+
+#[test]
+fn opcode_breakpoints_match_patterns() {
+    let mut chip8 = machine();
+    let mut debugger = Debugger::new();
+    assert_eq!(
+        debugger.execute(&mut chip8, "breakop 1???"),
+        "Opcode breakpoint 1???\n"
+    );
+    debugger.execute(&mut chip8, "c");
+    let (stop, executed) = run(&mut debugger, &mut chip8);
+    assert_eq!(stop.to_string(), "Opcode breakpoint 1???");
+    assert_eq!((executed, chip8.pc()), (3, 0x206));
+}
+
+#[test]
+fn registers_and_memory_can_be_changed() {
+    let mut chip8 = machine();
+    let mut debugger = Debugger::new();
+    debugger.execute(&mut chip8, "set v3 7f");
+    debugger.execute(&mut chip8, "set I 300");
+    debugger.execute(&mut chip8, "set pc 202");
+    debugger.execute(&mut chip8, "write 300 de ad");
+    assert_eq!(chip8.registers()[3], 0x7F);
+    assert_eq!(chip8.index_register(), 0x300);
+    assert_eq!(chip8.pc(), 0x202);
+    assert_eq!(&chip8.memory()[0x300..0x302], [0xDE, 0xAD]);
+
+    let regs = debugger.execute(&mut chip8, "regs");
+    assert!(regs.contains("V3=7F"));
+    assert!(regs.contains("I=0300 PC=0202"));
+    assert!(debugger
+        .execute(&mut chip8, "mem 300 2")
+        .starts_with("0300  DE AD"));
+    assert_eq!(
+        debugger
+            .execute(&mut chip8, &format!("mem ff0 {}", usize::MAX))
+            .lines()
+            .count(),
+        1
+    );
+    assert!(debugger
+        .execute(&mut chip8, "write ffff 1 2")
+        .contains("out of bounds"));
+    assert!(debugger
+        .execute(&mut chip8, "set v10 1")
+        .contains("Unknown register"));
+}
+
+#[test]
+fn list_marks_the_current_instruction() {
+    let mut chip8 = machine();
+    let mut debugger = Debugger::new();
+    let listing = debugger.execute(&mut chip8, "list 200 4");
+    let lines: Vec<&str> = listing.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert_eq!(lines[0], "=> 0x200  7001  ADD V0, 0x01");
+    assert_eq!(lines[3], "   0x206  1200  JP 0x200");
+
+    // Typos in the count are reported instead of overflowing
+    assert!(debugger
+        .execute(&mut chip8, "l 200 40000")
+        .contains("at most 2048"));
+}
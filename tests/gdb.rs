@@ -0,0 +1,189 @@
+use rust_chip8::chip8::{Chip8, Platform, Quirks, START_ADDR};
+use rust_chip8::gdb::{GdbStub, Session};
+use std::io::{Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::thread::{self, JoinHandle};
+
+// 0x200: V0 += 1, 0x202: V1 += 2, 0x204: draw, 0x206: jump 0x200
+const PROGRAM: [u8; 8] = [0x70, 0x01, 0x71, 0x02, 0xD0, 0x11, 0x12, 0x00];
+
+// A scripted client talking to a stub served from another thread
+struct Client {
+    stream: TcpStream,
+    server: JoinHandle<Session>,
+}
+
+impl Client {
+    fn connect(program: &'static [u8]) -> Self {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let mut chip8 = Chip8::new(Platform::Chip8, Quirks::chip48());
+        chip8.load(program, START_ADDR).unwrap();
+        let server = thread::spawn(move || {
+            let mut stub = GdbStub::accept(&listener, 10).unwrap();
+            stub.run(&mut chip8).unwrap()
+        });
+        let stream = TcpStream::connect(addr).unwrap();
+        Self { stream, server }
+    }
+
+    fn send(&mut self, packet: &str) {
+        let sum = packet.bytes().fold(0u8, |sum, byte| sum.wrapping_add(byte));
+        write!(self.stream, "${}#{:02x}", packet, sum).unwrap();
+        assert_eq!(self.read_byte(), b'+', "{} was not acknowledged", packet);
+    }
+
+    fn reply(&mut self) -> String {
+        assert_eq!(self.read_byte(), b'$');
+        let mut data = Vec::new();
+        loop {
+            match self.read_byte() {
+                b'#' => break,
+                byte => data.push(byte),
+            }
+        }
+        let sum = [self.read_byte(), self.read_byte()];
+        let sum = u8::from_str_radix(std::str::from_utf8(&sum).unwrap(), 16).unwrap();
+        assert_eq!(
+            sum,
+            data.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte))
+        );
+        self.stream.write_all(b"+").unwrap();
+        String::from_utf8(data).unwrap()
+    }
+
+    fn request(&mut self, packet: &str) -> String {
+        self.send(packet);
+        self.reply()
+    }
+
+    fn read_byte(&mut self) -> u8 {
+        let mut byte = [0];
+        self.stream.read_exact(&mut byte).unwrap();
+        byte[0]
+    }
+
+    fn finish(self) -> Session {
+        drop(self.stream);
+        self.server.join().unwrap()
+    }
+}
+
+#[test]
+fn registers_and_memory() {
+    let mut client = Client::connect(&PROGRAM);
+    assert_eq!(client.request("?"), "S05");
+
+    // V0-VF, then I and PC little endian, then SP, DT and ST
+    let registers = client.request("g");
+    assert_eq!(registers.len(), 2 * 16 + 4 + 4 + 2 * 3);
+    assert_eq!(&registers[32..40], "00000002");
+    assert_eq!(client.request("m200,4"), "70017102");
+
+    assert_eq!(client.request("P0=2a"), "OK");
+    assert_eq!(client.request("p0"), "2a");
+    assert_eq!(client.request("P10=0003"), "OK");
+    assert_eq!(client.request("p10"), "0003");
+    assert_eq!(client.request("M300,2:dead"), "OK");
+    assert_eq!(client.request("m300,2"), "dead");
+
+    assert_eq!(client.request("p15"), "E01");
+    assert_eq!(client.request("mffff0,2"), "E01");
+    assert_eq!(client.request("vMustReplyEmpty"), "");
+
+    client.send("k");
+    assert_eq!(client.finish(), Session::Killed);
+}
+
+#[test]
+fn breakpoints_and_stepping() {
+    let mut client = Client::connect(&PROGRAM);
+    assert_eq!(client.request("Z0,204,2"), "OK");
+    assert_eq!(client.request("c"), "S05");
+    assert_eq!(client.request("p11"), "0402");
+    assert_eq!(client.request("p0"), "01");
+
+    // Continuing leaves the breakpoint it is sitting on
+    assert_eq!(client.request("c"), "S05");
+    assert_eq!(client.request("p0"), "02");
+
+    assert_eq!(client.request("z0,204,2"), "OK");
+    assert_eq!(client.request("s"), "S05");
+    assert_eq!(client.request("p11"), "0602");
+    assert_eq!(client.request("s202"), "S05");
+    assert_eq!(client.request("p11"), "0402");
+
+    assert_eq!(client.request("D"), "OK");
+    assert_eq!(client.finish(), Session::Detached);
+}
+
+#[test]
+fn running_target_can_be_interrupted() {
+    let mut client = Client::connect(&PROGRAM);
+    client.send("c");
+    client.stream.write_all(&[0x03]).unwrap();
+    assert_eq!(client.reply(), "S02");
+    assert_eq!(client.finish(), Session::Disconnected);
+}
+
+#[test]
+fn packets_sent_while_running_are_kept() {
+    let mut client = Client::connect(&PROGRAM);
+    client.send("c");
+    client.stream.write_all(b"$p11#d2\x03").unwrap();
+    assert_eq!(client.reply(), "S02");
+    // The register read queued up before the interrupt is still answered
+    assert_eq!(client.read_byte(), b'+');
+    assert_eq!(client.reply().len(), 4);
+    client.send("k");
+    assert_eq!(client.finish(), Session::Killed);
+}
+
+#[test]
+fn hanging_up_on_a_running_target_ends_the_session() {
+    let mut client = Client::connect(&PROGRAM);
+    client.send("c");
+    assert_eq!(client.finish(), Session::Disconnected);
+}
+
+#[test]
+fn bad_opcodes_stop_the_target() {
+    let mut client = Client::connect(&[0x5F, 0x01]);
+    assert_eq!(client.request("c"), "S04");
+    // The program counter has already moved past the fetched opcode
+    assert_eq!(client.request("p11"), "0202");
+    client.send("k");
+    assert_eq!(client.finish(), Session::Killed);
+}
+
+#[test]
+fn target_description_is_served() {
+    let mut client = Client::connect(&PROGRAM);
+    assert!(client
+        .request("qSupported:xmlRegisters=i386")
+        .contains("qXfer:features:read+"));
+
+    let mut xml = String::new();
+    loop {
+        let chunk = client.request(&format!(
+            "qXfer:features:read:target.xml:{:x},40",
+            xml.len()
+        ));
+        xml.push_str(&chunk[1..]);
+        if chunk.starts_with('l') {
+            break;
+        }
+        assert!(chunk.starts_with('m'));
+    }
+    assert!(xml.contains(r#"<reg name="pc" bitsize="16" type="code_ptr"/>"#));
+    assert!(xml.trim_end().ends_with("</target>"));
+    // A length running past the end of the address space is cut short too
+    let huge = client.request(&format!(
+        "qXfer:features:read:target.xml:10,{:x}",
+        usize::MAX
+    ));
+    assert!(huge.starts_with('l'));
+    assert_eq!(client.request("M300,3:abcde"), "E01");
+    client.send("k");
+    assert_eq!(client.finish(), Session::Killed);
+}
@@ -0,0 +1,77 @@
+use rust_chip8::asm::assemble;
+use rust_chip8::chip8::Platform;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+// Draws a 0 in the top left corner and jumps to itself
+const ZERO: &str = "v0 := 0 i := hex v0 sprite v0 v0 5 : done jump done";
+
+// Writes `source` assembled to a ROM file named after the test
+fn rom(name: &str, source: &str) -> PathBuf {
+    let path = env::temp_dir().join(format!(
+        "chip8-headless-{}-{}.ch8",
+        name,
+        std::process::id()
+    ));
+    fs::write(&path, assemble(source, Platform::Chip8).unwrap()).unwrap();
+    path
+}
+
+fn headless(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_chip8-headless"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn runs_until_the_self_jump_and_dumps_the_screen() {
+    let path = rom("zero", ZERO);
+    let output = headless(&["--until-loop", "--ascii", path.to_str().unwrap()]);
+    fs::remove_file(&path).unwrap();
+    assert_eq!(output.status.code(), Some(0));
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let rows: Vec<&str> = stdout.lines().collect();
+    assert_eq!(&rows[0][..6], "####..");
+    assert_eq!(&rows[1][..6], "#..#..");
+    assert_eq!(&rows[4][..6], "####..");
+    assert!(rows[5].chars().all(|c| c == '.'));
+    assert_eq!(rows[32], "frame: 0");
+    assert!(rows[33].starts_with("pc: 0x0206"), "{}", rows[33]);
+}
+
+#[test]
+fn exit_codes() {
+    let path = rom("loop", ZERO);
+    let path = path.to_str().unwrap();
+    // Asked to stop at a self-jump that a ROM waiting on a key never reaches
+    let waiting = rom("waiting", "v0 := key : done jump done");
+    let waiting = waiting.to_str().unwrap();
+    let bad = rom("bad", "0x5F 0x01");
+    let bad = bad.to_str().unwrap();
+
+    assert_eq!(headless(&["--frames", "3", path]).status.code(), Some(0));
+    assert_eq!(
+        headless(&["--until-loop", "--frames", "3", waiting])
+            .status
+            .code(),
+        Some(3)
+    );
+    let output = headless(&[bad]);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unknown opcode 0x5F01"));
+
+    // A mistyped option isn't taken for the ROM
+    let output = headless(&["--frame", "10", path]);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8_lossy(&output.stderr).starts_with("Unknown option --frame"));
+    assert_eq!(headless(&[path, path]).status.code(), Some(2));
+    assert_eq!(headless(&["missing.ch8"]).status.code(), Some(2));
+
+    for rom in [path, waiting, bad] {
+        fs::remove_file(rom).unwrap();
+    }
+}
@@ -0,0 +1,92 @@
+use rust_chip8::chip8::{Chip8, Platform, Quirks, START_ADDR};
+use rust_chip8::config::Config;
+use rust_chip8::input::{Input, Source};
+use rust_chip8::keymap::Keymap;
+use rust_chip8::sha1::sha1_hex;
+
+const ROM: &[u8] = &[0x12, 0x00];
+
+#[test]
+fn keys_stay_down_until_every_source_lets_go() {
+    let pad = Source::Controller(3);
+    let mut input = Input::default();
+    input.press(Source::Keyboard, 5);
+    input.press(pad, 5);
+    input.release(Source::Keyboard, 5);
+    assert!(input.is_down(5));
+    input.release(pad, 5);
+    assert!(!input.is_down(5));
+
+    // Releasing what was never pressed changes nothing
+    input.press(pad, 8);
+    input.release(Source::Keyboard, 8);
+    assert!(input.is_down(8));
+
+    // An unplugged controller lets go of everything it held
+    input.press(pad, 2);
+    input.press(Source::Keyboard, 2);
+    input.release_all(pad);
+    assert!(!input.is_down(8));
+    assert!(input.is_down(2));
+}
+
+#[test]
+fn held_keys_reach_the_machine() {
+    // Wait for a key and store it in V0
+    let mut chip8 = Chip8::new(Platform::Chip8, Quirks::default());
+    chip8.load(&[0xF0, 0x0A, 0x12, 0x02], START_ADDR).unwrap();
+    let mut input = Input::default();
+    input.press(Source::Controller(0), 0xB);
+    input.apply(&mut chip8);
+    chip8.tick().unwrap();
+    input.release(Source::Controller(0), 0xB);
+    input.apply(&mut chip8);
+    chip8.tick().unwrap();
+    assert_eq!(chip8.pc(), 0x202);
+    assert_eq!(chip8.registers()[0], 0xB);
+}
+
+#[test]
+fn buttons_layer_like_keys() {
+    let defaults = Keymap::buttons();
+    assert_eq!(defaults.key("dpup"), Some(5));
+    assert_eq!(defaults.key("A"), Some(6));
+    assert_eq!(defaults.key("start"), None);
+
+    let text = format!(
+        "buttons = [\"start = F\"]\n[rom.{}]\nbuttons = [\"dpup = 2\", \"dpdown = 8\"]",
+        sha1_hex(ROM)
+    );
+    let config = Config::parse(&text).unwrap();
+    let buttons = config.settings_for(ROM).button_map();
+    assert_eq!(buttons.key("dpup"), Some(2));
+    assert_eq!(buttons.key("start"), Some(0xF));
+    assert_eq!(buttons.key("a"), Some(6));
+    assert_eq!(
+        config.settings_for(&[0x00, 0xE0]).button_map().key("dpup"),
+        Some(5)
+    );
+    assert!(Config::parse("buttons = [\"dpup\"]").is_err());
+}
+
+#[test]
+fn buttons_press_keys_through_the_rom_button_map() {
+    let text = format!("[rom.{}]\nbuttons = [\"dpup = 2\"]", sha1_hex(ROM));
+    let buttons = Config::parse(&text).unwrap().settings_for(ROM).button_map();
+    let mut input = Input::default();
+    input.button(1, "dpup", true, &buttons);
+    input.button(1, "a", true, &buttons);
+    input.button(1, "start", true, &buttons);
+    assert!(input.is_down(2));
+    assert!(!input.is_down(5));
+    assert!(input.is_down(6));
+
+    // Another controller letting go of the same button changes nothing
+    input.button(2, "dpup", false, &buttons);
+    assert!(input.is_down(2));
+    input.button(1, "dpup", false, &buttons);
+    assert!(!input.is_down(2));
+
+    input.release_all(Source::Controller(1));
+    assert!(!input.is_down(6));
+}
@@ -0,0 +1,529 @@
+use rust_chip8::asm::assemble;
+use rust_chip8::chip8::{Chip8, Chip8Error, Instruction, Platform, Quirks, START_ADDR};
+
+fn machine(platform: Platform, quirks: Quirks, source: &str) -> Chip8 {
+    let rom = assemble(source, platform).unwrap_or_else(|err| panic!("{}", err));
+    let mut chip8 = Chip8::new(platform, quirks);
+    chip8.load(&rom, START_ADDR).unwrap();
+    chip8
+}
+
+fn run(chip8: &mut Chip8, ticks: usize) {
+    for _ in 0..ticks {
+        chip8.tick().unwrap();
+    }
+}
+
+// Run every instruction of `source` once on a CHIP-48 machine
+fn chip48(source: &str, ticks: usize) -> Chip8 {
+    let mut chip8 = machine(Platform::Chip8, Quirks::chip48(), source);
+    run(&mut chip8, ticks);
+    chip8
+}
+
+fn vf(chip8: &Chip8) -> u8 {
+    chip8.registers()[0xF]
+}
+
+fn lit_pixels(chip8: &Chip8) -> Vec<(usize, usize)> {
+    let (screen, width, _) = chip8.get_screen();
+    (0..screen.len())
+        .filter(|idx| screen[*idx] != 0)
+        .map(|idx| (idx % width, idx / width))
+        .collect()
+}
+
+#[test]
+fn decode_and_encode_agree() {
+    for opcode in 0..=u16::MAX {
+        if let Ok(instruction) = Instruction::decode(opcode) {
+            assert_eq!(instruction.encode(), opcode, "{:04X}", opcode);
+        }
+    }
+    assert!(Instruction::decode(0x5001).is_err());
+    assert!(Instruction::decode(0x8008).is_err());
+    assert!(Instruction::decode(0xE000).is_err());
+}
+
+#[test]
+fn set_and_add_constant() {
+    let chip8 = chip48("v3 := 0xFE v3 += 3 vf := 7 v4 := 1 v4 += 0xFF", 5);
+    assert_eq!(chip8.registers()[3], 0x01);
+    // 7XNN wraps without touching the carry flag
+    assert_eq!(vf(&chip8), 7);
+    assert_eq!(chip8.registers()[4], 0);
+}
+
+#[test]
+fn jump_call_and_return() {
+    let chip8 = chip48(
+        "jump start : sub v1 := 2 return : start sub v2 := 3 v3 := 4",
+        5,
+    );
+    assert_eq!(&chip8.registers()[1..4], [2, 3, 0]);
+    assert!(chip8.stack().is_empty());
+
+    let mut chip8 = machine(
+        Platform::Chip8,
+        Quirks::chip48(),
+        ": loop sub jump loop : sub sub",
+    );
+    let err = (0..100).find_map(|_| chip8.tick().err());
+    assert_eq!(err, Some(Chip8Error::StackOverflow));
+
+    let mut chip8 = machine(Platform::Chip8, Quirks::chip48(), "return");
+    assert_eq!(chip8.tick(), Err(Chip8Error::StackUnderflow));
+}
+
+#[test]
+fn skips() {
+    // Each taken skip jumps over a `v5 += 1`, so only the untaken ones count
+    let chip8 = chip48(
+        "v0 := 1 v1 := 1 v2 := 2
+        if v0 != 1 then v5 += 1
+        if v0 == 1 then v5 += 1
+        if v0 != 2 then v5 += 1
+        if v0 == 2 then v5 += 1
+        if v0 != v1 then v5 += 1
+        if v0 == v1 then v5 += 1
+        if v0 != v2 then v5 += 1
+        if v0 == v2 then v5 += 1",
+        3 + 8 + 4,
+    );
+    assert_eq!(chip8.registers()[5], 4);
+    assert_eq!(chip8.pc(), 0x200 + 2 * 3 + 4 * 8);
+}
+
+#[test]
+fn xo_chip_skips_over_long_index() {
+    let mut chip8 = machine(
+        Platform::XoChip,
+        Quirks::xo_chip(),
+        "if v0 != 0 then i := long 0x1234 v1 := 1",
+    );
+    run(&mut chip8, 2);
+    assert_eq!(chip8.index_register(), 0);
+    assert_eq!(chip8.registers()[1], 1);
+    assert_eq!(chip8.pc(), 0x208);
+}
+
+#[test]
+fn extensions_are_unknown_without_their_platform() {
+    // On CHIP-8 F000 is an ordinary bad opcode, so a skip only steps over
+    // its first word and runs the second
+    let rom = [0x30, 0x00, 0xF0, 0x00, 0x61, 0x05];
+    let mut chip8 = Chip8::new(Platform::Chip8, Quirks::chip48());
+    chip8.load(&rom, START_ADDR).unwrap();
+    run(&mut chip8, 2);
+    assert_eq!(chip8.registers()[1], 5);
+    assert_eq!(chip8.pc(), 0x206);
+
+    let mut chip8 = Chip8::new(Platform::Chip8, Quirks::chip48());
+    chip8.load(&rom[2..], START_ADDR).unwrap();
+    assert_eq!(chip8.tick(), Err(Chip8Error::UnknownOpcode(0xF000)));
+
+    let mut chip8 = Chip8::new(Platform::SuperChip, Quirks::superchip());
+    chip8.load(&[0x00, 0xFF, 0xF0, 0x00], START_ADDR).unwrap();
+    run(&mut chip8, 1);
+    assert_eq!(chip8.tick(), Err(Chip8Error::UnknownOpcode(0xF000)));
+}
+
+#[test]
+fn logic_operations() {
+    let source = "v0 := 0b1100 v1 := 0b1010 vf := 9
+        v2 := v0 v2 |= v1
+        v3 := v0 v3 &= v1
+        v4 := v0 v4 ^= v1";
+    let chip8 = chip48(source, 9);
+    assert_eq!(&chip8.registers()[2..5], [0b1110, 0b1000, 0b0110]);
+    assert_eq!(vf(&chip8), 9);
+
+    // The VIP's logic operations clobber VF
+    let mut chip8 = machine(Platform::Chip8, Quirks::cosmac_vip(), source);
+    run(&mut chip8, 5);
+    assert_eq!(vf(&chip8), 0);
+}
+
+#[test]
+fn add_sets_carry() {
+    let chip8 = chip48("v0 := 200 v1 := 100 v0 += v1", 3);
+    assert_eq!((chip8.registers()[0], vf(&chip8)), (44, 1));
+
+    let chip8 = chip48("v0 := 200 v1 := 55 vf := 1 v0 += v1", 4);
+    assert_eq!((chip8.registers()[0], vf(&chip8)), (255, 0));
+
+    // The flag is written last, so it wins when VF is the destination
+    let chip8 = chip48("vf := 200 v1 := 100 vf += v1", 3);
+    assert_eq!(vf(&chip8), 1);
+}
+
+#[test]
+fn subtract_sets_not_borrow() {
+    let chip8 = chip48("v0 := 10 v1 := 3 v0 -= v1", 3);
+    assert_eq!((chip8.registers()[0], vf(&chip8)), (7, 1));
+
+    let chip8 = chip48("v0 := 3 v1 := 10 v0 -= v1", 3);
+    assert_eq!((chip8.registers()[0], vf(&chip8)), (249, 0));
+
+    // Equal operands don't borrow
+    let chip8 = chip48("v0 := 5 v1 := 5 v0 -= v1", 3);
+    assert_eq!((chip8.registers()[0], vf(&chip8)), (0, 1));
+
+    let chip8 = chip48("vf := 10 v1 := 3 vf -= v1", 3);
+    assert_eq!(vf(&chip8), 1);
+}
+
+#[test]
+fn reverse_subtract_sets_not_borrow() {
+    let chip8 = chip48("v0 := 3 v1 := 10 v0 =- v1", 3);
+    assert_eq!((chip8.registers()[0], vf(&chip8)), (7, 1));
+
+    let chip8 = chip48("v0 := 10 v1 := 3 v0 =- v1", 3);
+    assert_eq!((chip8.registers()[0], vf(&chip8)), (249, 0));
+
+    let chip8 = chip48("vf := 10 v1 := 3 vf =- v1", 3);
+    assert_eq!(vf(&chip8), 0);
+}
+
+#[test]
+fn shifts_set_the_shifted_out_bit() {
+    let chip8 = chip48("v0 := 0b10000011 v0 >>= v1", 2);
+    assert_eq!((chip8.registers()[0], vf(&chip8)), (0b01000001, 1));
+
+    let chip8 = chip48("v0 := 0b10000010 v0 <<= v1", 2);
+    assert_eq!((chip8.registers()[0], vf(&chip8)), (0b00000100, 1));
+
+    let chip8 = chip48("v0 := 0b01000010 v0 <<= v1", 2);
+    assert_eq!((chip8.registers()[0], vf(&chip8)), (0b10000100, 0));
+
+    let chip8 = chip48("vf := 0b11 vf >>= vf", 2);
+    assert_eq!(vf(&chip8), 1);
+
+    // The VIP shifts VY into VX
+    let mut chip8 = machine(
+        Platform::Chip8,
+        Quirks::cosmac_vip(),
+        "v0 := 0xFF v1 := 0b110 v0 >>= v1 v2 := 0x80 v2 <<= v2",
+    );
+    run(&mut chip8, 5);
+    assert_eq!(chip8.registers()[0], 0b011);
+    assert_eq!((chip8.registers()[2], vf(&chip8)), (0, 1));
+}
+
+#[test]
+fn index_operations() {
+    let chip8 = chip48("i := 0x123 v0 := 0x10 i += v0", 3);
+    assert_eq!(chip8.index_register(), 0x133);
+
+    let chip8 = chip48("v0 := 0xA i := hex v0", 2);
+    assert_eq!(chip8.index_register(), 0xA * 5);
+    assert_eq!(&chip8.memory()[0x32..0x37], [0xF0, 0x90, 0xF0, 0x90, 0x90]);
+
+    let mut chip8 = machine(
+        Platform::XoChip,
+        Quirks::xo_chip(),
+        "i := long 0xFFF0 v0 := 0x20 i += v0",
+    );
+    run(&mut chip8, 3);
+    assert_eq!(chip8.index_register(), 0x10);
+}
+
+#[test]
+fn jump_with_offset() {
+    // BNNN jumps to NNN + V0, with the quirk BXNN jumps to XNN + VX
+    let source = "v0 := 4 v2 := 8 jump0 0x230";
+    let mut chip8 = machine(Platform::Chip8, Quirks::cosmac_vip(), source);
+    run(&mut chip8, 3);
+    assert_eq!(chip8.pc(), 0x234);
+
+    let chip8 = chip48(source, 3);
+    assert_eq!(chip8.pc(), 0x238);
+}
+
+#[test]
+fn random_is_masked_and_seeded() {
+    let source = "v0 := random 0x0F v1 := random 0xFF v2 := random 0";
+    let mut first = machine(Platform::Chip8, Quirks::chip48(), source);
+    first.set_seed(42);
+    run(&mut first, 3);
+    let mut second = machine(Platform::Chip8, Quirks::chip48(), source);
+    second.set_seed(42);
+    run(&mut second, 3);
+
+    assert_eq!(first.registers(), second.registers());
+    assert!(first.registers()[0] <= 0x0F);
+    assert_eq!(first.registers()[2], 0);
+}
+
+#[test]
+fn draw_sets_collision_flag() {
+    let mut chip8 = machine(
+        Platform::Chip8,
+        Quirks::chip48(),
+        "i := shape v0 := 2 v1 := 3
+        sprite v0 v1 2 v2 := vf
+        sprite v0 v1 1 v3 := vf
+        sprite v0 v1 1
+        : shape 0b10000001 0b01000000",
+    );
+    run(&mut chip8, 5);
+    assert_eq!(lit_pixels(&chip8), [(2, 3), (9, 3), (3, 4)]);
+    assert_eq!(chip8.registers()[2], 0);
+
+    // Drawing the first row again turns its pixels off
+    run(&mut chip8, 2);
+    assert_eq!(chip8.registers()[3], 1);
+    assert_eq!(lit_pixels(&chip8), [(3, 4)]);
+
+    // Overlapping without erasing anything leaves VF clear
+    run(&mut chip8, 1);
+    assert_eq!(vf(&chip8), 0);
+}
+
+#[test]
+fn draw_wraps_the_start_and_clips_the_rest() {
+    let source = "i := shape v0 := 67 v1 := 30 sprite v0 v1 3 : shape 0xFF 0x80 0x80";
+    let chip8 = chip48(source, 4);
+    // 67 wraps to 3, then the sprite is cut off at the bottom edge
+    let pixels = lit_pixels(&chip8);
+    assert_eq!(pixels.len(), 9);
+    assert!(pixels.contains(&(3, 30)) && pixels.contains(&(10, 30)) && pixels.contains(&(3, 31)));
+
+    let mut chip8 = machine(Platform::XoChip, Quirks::xo_chip(), source);
+    run(&mut chip8, 4);
+    // Without clipping the last row wraps to the top
+    assert!(lit_pixels(&chip8).contains(&(3, 0)));
+}
+
+#[test]
+fn draw_with_zero_rows_is_only_big_with_super_chip() {
+    let source = "i := shape sprite v0 v0 0 : shape 0xFF 0xFF";
+    // Plain CHIP-8 has no 16x16 sprites, so DXY0 draws nothing
+    let chip8 = chip48(source, 2);
+    assert!(lit_pixels(&chip8).is_empty());
+
+    let mut chip8 = machine(Platform::SuperChip, Quirks::superchip(), source);
+    run(&mut chip8, 2);
+    assert_eq!(lit_pixels(&chip8).len(), 16);
+}
+
+#[test]
+fn draw_waits_for_vblank_with_the_quirk() {
+    let mut chip8 = machine(
+        Platform::Chip8,
+        Quirks::cosmac_vip(),
+        "sprite v0 v0 1 v1 := 1",
+    );
+    run(&mut chip8, 3);
+    assert!(chip8.is_waiting_for_vblank());
+    assert_eq!(chip8.registers()[1], 0);
+    chip8.tick_timers();
+    run(&mut chip8, 1);
+    assert_eq!(chip8.registers()[1], 1);
+}
+
+#[test]
+fn clear_screen() {
+    let chip8 = chip48("sprite v0 v0 5 clear", 2);
+    assert!(lit_pixels(&chip8).is_empty());
+}
+
+#[test]
+fn keys() {
+    let source = "v0 := 7
+        if v0 -key then v1 := 1
+        if v0 key then v2 := 1
+        v3 := key";
+    let mut chip8 = machine(Platform::Chip8, Quirks::chip48(), source);
+    chip8.keypress(7, true);
+    run(&mut chip8, 5);
+    assert_eq!(&chip8.registers()[1..4], [0, 1, 7]);
+
+    // Without a key held FX0A keeps executing itself
+    let mut chip8 = machine(Platform::Chip8, Quirks::chip48(), "v3 := key v4 := 1");
+    run(&mut chip8, 10);
+    assert_eq!(chip8.pc(), 0x200);
+    chip8.keypress(0xC, true);
+    run(&mut chip8, 2);
+    assert_eq!(&chip8.registers()[3..5], [0xC, 1]);
+
+    // Waiting at the very end of XO-CHIP's memory, where the fetch wrapped pc to 0
+    let mut chip8 = Chip8::new(Platform::XoChip, Quirks::xo_chip());
+    chip8.write_memory(0xFFFE, &[0xF0, 0x0A]).unwrap();
+    chip8.set_pc(0xFFFE);
+    run(&mut chip8, 3);
+    assert_eq!(chip8.pc(), 0xFFFE);
+
+    let mut chip8 = machine(
+        Platform::Chip8,
+        Quirks::chip48(),
+        "v0 := 0x10 if v0 key then",
+    );
+    run(&mut chip8, 1);
+    assert_eq!(chip8.tick(), Err(Chip8Error::InvalidKey(0x10)));
+}
+
+#[test]
+fn timers() {
+    let mut chip8 = machine(
+        Platform::Chip8,
+        Quirks::chip48(),
+        "v0 := 3 delay := v0 buzzer := v0 v1 := delay",
+    );
+    run(&mut chip8, 3);
+    assert_eq!((chip8.delay_timer(), chip8.sound_timer()), (3, 3));
+    chip8.tick_timers();
+    chip8.tick_timers();
+    run(&mut chip8, 1);
+    assert_eq!(chip8.registers()[1], 1);
+    chip8.tick_timers();
+    chip8.tick_timers();
+    assert_eq!((chip8.delay_timer(), chip8.sound_timer()), (0, 0));
+}
+
+#[test]
+fn binary_coded_decimal() {
+    for (value, digits) in [
+        (0, [0, 0, 0]),
+        (9, [0, 0, 9]),
+        (137, [1, 3, 7]),
+        (255, [2, 5, 5]),
+    ] {
+        let chip8 = chip48(&format!("i := 0x300 v5 := {} bcd v5", value), 3);
+        assert_eq!(&chip8.memory()[0x300..0x303], digits, "{}", value);
+        assert_eq!(chip8.index_register(), 0x300);
+    }
+    let mut chip8 = machine(Platform::Chip8, Quirks::chip48(), "i := 0xFFE bcd v0");
+    run(&mut chip8, 1);
+    assert!(matches!(
+        chip8.tick(),
+        Err(Chip8Error::MemoryOutOfBounds { addr: 0x1000, .. })
+    ));
+}
+
+#[test]
+fn store_and_load_registers() {
+    let source = "v0 := 1 v1 := 2 v2 := 3 i := 0x300 save v2 i := 0x300 load v1";
+    let chip8 = chip48(
+        &format!("{} v0 := 9 v1 := 9 i := 0x300 load v1", source),
+        11,
+    );
+    assert_eq!(&chip8.memory()[0x300..0x304], [1, 2, 3, 0]);
+    assert_eq!(&chip8.registers()[..3], [1, 2, 3]);
+    // CHIP-48 moves I by X, one short of the VIP
+    assert_eq!(chip8.index_register(), 0x301);
+
+    let mut chip8 = machine(Platform::SuperChip, Quirks::superchip(), source);
+    run(&mut chip8, 5);
+    // SUPER-CHIP 1.1 leaves I alone
+    assert_eq!(chip8.index_register(), 0x300);
+
+    let mut chip8 = machine(Platform::Chip8, Quirks::cosmac_vip(), source);
+    run(&mut chip8, 5);
+    assert_eq!(chip8.index_register(), 0x303);
+    run(&mut chip8, 2);
+    assert_eq!(chip8.index_register(), 0x302);
+}
+
+#[test]
+fn super_chip_scrolling_and_resolution() {
+    let mut chip8 = machine(
+        Platform::SuperChip,
+        Quirks::superchip(),
+        "hires i := dot v0 := 8 sprite v0 v0 1 scroll-down 2 scroll-right scroll-left scroll-left lores
+        : dot 0x80",
+    );
+    run(&mut chip8, 4);
+    assert_eq!(chip8.get_screen().1, 128);
+    assert_eq!(lit_pixels(&chip8), [(8, 8)]);
+    run(&mut chip8, 1);
+    assert_eq!(lit_pixels(&chip8), [(8, 10)]);
+    run(&mut chip8, 1);
+    assert_eq!(lit_pixels(&chip8), [(12, 10)]);
+    run(&mut chip8, 2);
+    assert_eq!(lit_pixels(&chip8), [(4, 10)]);
+    // Switching resolution clears the display
+    run(&mut chip8, 1);
+    assert_eq!(chip8.get_screen().1, 64);
+    assert!(lit_pixels(&chip8).is_empty());
+}
+
+#[test]
+fn super_chip_sprites_fonts_and_flags() {
+    let mut chip8 = machine(
+        Platform::SuperChip,
+        Quirks::superchip(),
+        "i := shape sprite v0 v0 0
+        v0 := 3 i := bighex v0
+        v0 := 0xAA v1 := 0xBB saveflags v1 v0 := 0 v1 := 0 loadflags v1
+        exit v2 := 1
+        : shape 0xFF 0xFF",
+    );
+    run(&mut chip8, 2);
+    // A 16x16 sprite with only its first row set
+    assert_eq!(
+        lit_pixels(&chip8),
+        (0..16).map(|x| (x, 0)).collect::<Vec<_>>()
+    );
+    run(&mut chip8, 2);
+    let big_three = chip8.index_register() as usize;
+    assert_eq!(chip8.memory()[big_three], 0xFF);
+    run(&mut chip8, 6);
+    assert_eq!(&chip8.registers()[..2], [0xAA, 0xBB]);
+    run(&mut chip8, 5);
+    assert!(chip8.has_exited());
+    assert_eq!(chip8.registers()[2], 0);
+}
+
+#[test]
+fn xo_chip_register_ranges() {
+    let mut chip8 = machine(
+        Platform::XoChip,
+        Quirks::xo_chip(),
+        "v1 := 1 v2 := 2 v3 := 3 i := 0x300
+        save v1 - v3 save v3 - v1 :byte 0x00 0x00
+        load v1 - v3",
+    );
+    run(&mut chip8, 6);
+    assert_eq!(&chip8.memory()[0x300..0x303], [3, 2, 1]);
+    assert_eq!(chip8.index_register(), 0x300);
+    run(&mut chip8, 2);
+    assert_eq!(&chip8.registers()[1..4], [3, 2, 1]);
+}
+
+#[test]
+fn xo_chip_planes_and_scroll_up() {
+    let mut chip8 = machine(
+        Platform::XoChip,
+        Quirks::xo_chip(),
+        "plane 3 i := shape v0 := 5 sprite v0 v0 1
+        plane 2 scroll-up 1 plane 1 clear
+        : shape 0x80 0x80",
+    );
+    run(&mut chip8, 4);
+    let (screen, width, _) = chip8.get_screen();
+    // Both planes read their own row of sprite data
+    assert_eq!(screen[5 + 5 * width], 3);
+    run(&mut chip8, 2);
+    let (screen, width, _) = chip8.get_screen();
+    assert_eq!((screen[5 + 5 * width], screen[5 + 4 * width]), (1, 2));
+    run(&mut chip8, 2);
+    assert_eq!(lit_pixels(&chip8), [(5, 4)]);
+}
+
+#[test]
+fn xo_chip_audio() {
+    let mut chip8 = machine(
+        Platform::XoChip,
+        Quirks::xo_chip(),
+        "i := pattern audio v0 := 112 pitch := v0
+        : pattern 0xFF 0x00 0xFF 0x00 0xFF 0x00 0xFF 0x00
+                  0xFF 0x00 0xFF 0x00 0xFF 0x00 0xFF 0x01",
+    );
+    assert_eq!(chip8.audio_pattern(), None);
+    run(&mut chip8, 4);
+    let pattern = chip8.audio_pattern().unwrap();
+    assert!(pattern.bit(0) && !pattern.bit(8) && pattern.bit(127));
+    // The default pitch of 64 plays at 4000 Hz, and every 48 steps is an octave
+    assert!((pattern.playback_rate() - 8000.0).abs() < 1.0);
+}
@@ -0,0 +1,58 @@
+use rust_chip8::config::Config;
+use rust_chip8::palette::Palette;
+use rust_chip8::phosphor::Phosphor;
+
+const BLACK: [u8; 3] = [0, 0, 0];
+const WHITE: [u8; 3] = [255, 255, 255];
+
+#[test]
+fn unlit_pixels_fade_and_lit_ones_snap() {
+    // 100 ms is 6 frames
+    let mut phosphor = Phosphor::new(Palette::default(), 100).unwrap();
+    phosphor.update(&[1, 0], 2, 1, 1);
+    assert_eq!(phosphor.color(0), WHITE);
+    assert_eq!(phosphor.color(1), BLACK);
+
+    // Erased and redrawn a frame later, as XOR drawing does
+    phosphor.update(&[0, 1], 2, 1, 1);
+    let fading = phosphor.color(0);
+    assert!(fading[0] > 128 && fading[0] < 255, "{:?}", fading);
+    assert_eq!(phosphor.color(1), WHITE);
+
+    phosphor.update(&[0, 1], 2, 1, 1);
+    assert!(phosphor.color(0)[0] < fading[0]);
+    // All but gone once the persistence time is up
+    phosphor.update(&[0, 1], 2, 1, 4);
+    assert!(phosphor.color(0)[0] <= 13, "{:?}", phosphor.color(0));
+    phosphor.update(&[0, 1], 2, 1, 60);
+    assert_eq!(phosphor.color(0), BLACK);
+}
+
+#[test]
+fn fades_towards_the_palette() {
+    // A light background with a dark foreground fades upwards
+    let lcd = Palette::from_name("lcd").unwrap();
+    let mut phosphor = Phosphor::new(lcd, 50).unwrap();
+    phosphor.update(&[3], 1, 1, 1);
+    assert_eq!(phosphor.color(0), lcd.colors[3]);
+    phosphor.update(&[0], 1, 1, 1);
+    assert!(phosphor.color(0)[1] > lcd.colors[3][1]);
+    phosphor.update(&[0], 1, 1, 600);
+    assert_eq!(phosphor.color(0), lcd.colors[0]);
+}
+
+#[test]
+fn new_resolutions_start_clean() {
+    let mut phosphor = Phosphor::new(Palette::default(), 1000).unwrap();
+    phosphor.update(&[1; 4], 2, 2, 1);
+    phosphor.update(&[0; 8], 4, 2, 1);
+    assert_eq!(phosphor.color(7), BLACK);
+}
+
+#[test]
+fn off_unless_asked_for() {
+    assert!(Phosphor::new(Palette::default(), 0).is_none());
+    let config = Config::parse("phosphor = 120").unwrap();
+    assert_eq!(config.defaults.phosphor, Some(120));
+    assert!(Config::parse("phosphor = 20000").is_err());
+}
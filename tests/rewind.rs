@@ -0,0 +1,83 @@
+use rust_chip8::rewind::{apply_delta, encode_delta, Rewind, DEFAULT_REWIND_BUDGET};
+
+// Turns `to` back into `from`
+fn round_trip(from: &[u8], to: &[u8]) -> Vec<u8> {
+    let delta = encode_delta(from, to);
+    let mut state = to.to_vec();
+    apply_delta(&mut state, &delta);
+    assert_eq!(state, from);
+    delta
+}
+
+#[test]
+fn deltas_round_trip() {
+    let state: Vec<u8> = (0..=255).collect();
+    // Nothing changed, nothing stored
+    assert!(round_trip(&state, &state).is_empty());
+
+    // Every byte changed: one run
+    let inverted: Vec<u8> = state.iter().map(|b| !b).collect();
+    let delta = round_trip(&state, &inverted);
+    // 0 unchanged, then 256 changed which takes two varint bytes
+    assert_eq!(&delta[..3], [0, 0x80, 0x02]);
+    assert_eq!(delta.len(), 3 + 256);
+
+    // Changes at both ends with a long unchanged run between them
+    let mut edges = state.clone();
+    edges[0] ^= 1;
+    edges[255] ^= 0xFF;
+    let delta = round_trip(&state, &edges);
+    assert_eq!(delta, [0, 1, 1, 0xFE, 0x01, 1, 0xFF]);
+
+    // Runs of several varint bytes
+    let big = vec![0u8; 70_000];
+    let mut far = big.clone();
+    far[69_999] = 1;
+    far[20_000..20_200].fill(7);
+    round_trip(&big, &far);
+    round_trip(&far, &big);
+}
+
+#[test]
+fn pop_steps_back_newest_first() {
+    let mut rewind = Rewind::new(DEFAULT_REWIND_BUDGET);
+    assert_eq!(rewind.pop(), None);
+    for frame in 0..5u8 {
+        rewind.push(vec![frame, 0, frame, 0]);
+    }
+    assert_eq!(rewind.len(), 4);
+    for frame in (0..4u8).rev() {
+        assert_eq!(rewind.pop(), Some(vec![frame, 0, frame, 0]));
+    }
+    assert_eq!(rewind.pop(), None);
+    assert_eq!(rewind.used(), 0);
+
+    // A state of another size, from another machine, starts over
+    rewind.push(vec![1, 2, 3, 4]);
+    rewind.push(vec![5, 6, 7, 8]);
+    rewind.push(vec![1; 8]);
+    assert!(rewind.is_empty());
+}
+
+#[test]
+fn oldest_frames_go_once_over_budget() {
+    // Every byte changes between frames, so each delta is a little over 64 KiB
+    const SIZE: usize = 64 * 1024;
+    let frames = 2 * DEFAULT_REWIND_BUDGET / SIZE;
+    let state = |frame: usize| vec![frame as u8; SIZE];
+
+    let mut rewind = Rewind::new(DEFAULT_REWIND_BUDGET);
+    for frame in 0..frames {
+        rewind.push(state(frame));
+        assert!(rewind.used() <= DEFAULT_REWIND_BUDGET);
+    }
+    let kept = rewind.len();
+    assert!(kept > 0 && kept < DEFAULT_REWIND_BUDGET / SIZE, "{}", kept);
+
+    // What's left is the most recent history
+    let newest = frames - 1;
+    for back in 1..=kept {
+        assert_eq!(rewind.pop(), Some(state(newest - back)));
+    }
+    assert_eq!(rewind.pop(), None);
+}
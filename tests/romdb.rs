@@ -0,0 +1,117 @@
+use rust_chip8::chip8::{Platform, Quirks};
+use rust_chip8::palette::Palette;
+use rust_chip8::romdb::{RomDatabase, RomDbError};
+use rust_chip8::sha1::sha1_hex;
+
+const PONG: &[u8] = &[0x6A, 0x02, 0x12, 0x00];
+const OCTO_GAME: &[u8] = &[0x00, 0xFF, 0x12, 0x02];
+
+// Laid out like programs.json in the CHIP-8 database
+fn database() -> RomDatabase {
+    let text = format!(
+        r##"[
+  {{
+    "title": "Pong édition",
+    "origin": {{ "type": "gamejam" }},
+    "roms": {{
+      "{pong}": {{
+        "file": "pong.ch8",
+        "platforms": ["megachip8", "originalChip8", "modernChip8"],
+        "quirkyPlatforms": {{ "originalChip8": {{ "vblank": false, "wrap": true }} }},
+        "tickrate": 30,
+        "keys": {{ "up": 1, "down": 4, "a": 10 }}
+      }}
+    }}
+  }},
+  {{
+    "title": "Octo Game",
+    "roms": {{
+      "{octo}": {{
+        "platforms": ["xochip"],
+        "startAddress": 512,
+        "colors": {{ "pixels": ["#000000", "#ff0000", "#00ff00", "#0000ff"], "buzzer": "#ffffff" }}
+      }}
+    }}
+  }},
+  {{ "title": "Not dumped yet", "roms": {{}} }}
+]"##,
+        pong = sha1_hex(PONG).to_uppercase(),
+        octo = sha1_hex(OCTO_GAME),
+    );
+    RomDatabase::parse(&text).unwrap()
+}
+
+#[test]
+fn known_roms_get_their_settings() {
+    let database = database();
+    assert_eq!(database.len(), 2);
+
+    let pong = database.lookup(PONG).unwrap();
+    assert_eq!(pong.title, "Pong \u{e9}dition");
+    // MEGA-CHIP isn't supported, so the next platform is used
+    assert_eq!(pong.settings.platform, Some(Platform::Chip8));
+    assert_eq!(
+        pong.settings.quirks,
+        Some(Quirks {
+            display_wait: false,
+            clip_sprites: false,
+            ..Quirks::cosmac_vip()
+        })
+    );
+    assert_eq!(pong.settings.instructions_per_second, Some(30 * 60));
+    assert_eq!(pong.settings.keymap, None);
+    let keymap = pong.settings.keymap_with_keys();
+    assert_eq!(keymap.key("Up"), Some(1));
+    assert_eq!(keymap.key("space"), Some(0xA));
+    // The usual keys keep working
+    assert_eq!(keymap.key("Q"), Some(4));
+    assert_eq!(pong.settings.button_map().key("dpup"), Some(1));
+    assert_eq!(pong.start_address, None);
+
+    let octo = database.lookup(OCTO_GAME).unwrap();
+    assert_eq!(octo.settings.platform, Some(Platform::XoChip));
+    assert_eq!(octo.settings.quirks, Some(Quirks::xo_chip()));
+    assert_eq!(
+        octo.settings.palette,
+        Palette::parse("#000000,#ff0000,#00ff00,#0000ff")
+    );
+    assert!(octo.settings.keys.is_empty());
+    assert_eq!(octo.start_address, Some(0x200));
+
+    assert!(database.lookup(&[0x00, 0xE0]).is_none());
+}
+
+#[test]
+fn embedded_database_parses() {
+    let database = RomDatabase::parse(include_str!("../data/chip-8-database/programs.json"))
+        .unwrap_or_else(|err| panic!("{}", err));
+    assert!(database.skipped().is_empty(), "{:?}", database.skipped());
+}
+
+#[test]
+fn broken_databases_are_reported() {
+    let err = RomDatabase::parse("[\n  {\"title\": \"x\",\n  }\n]").unwrap_err();
+    assert!(
+        matches!(err, RomDbError::Json(ref json) if json.line() == 3),
+        "{}",
+        err
+    );
+
+    // One bad entry doesn't take the rest of the database down with it
+    let database = RomDatabase::parse(&format!(
+        r#"[{{"title": "Slow", "roms": {{"{}": {{"tickrate": 0}}}}}},
+            {{"roms": {{}}}},
+            {{"title": "Fine", "roms": {{"{}": {{}}}}}}]"#,
+        sha1_hex(PONG),
+        sha1_hex(OCTO_GAME)
+    ))
+    .unwrap();
+    assert!(database.lookup(PONG).is_none());
+    assert_eq!(database.lookup(OCTO_GAME).unwrap().title, "Fine");
+    let skipped = database.skipped();
+    assert_eq!(skipped.len(), 2, "{:?}", skipped);
+    assert!(skipped[0].starts_with("Slow"), "{}", skipped[0]);
+    assert!(skipped[1].contains("no title"), "{}", skipped[1]);
+
+    assert!(RomDatabase::parse("{}").is_err());
+}
@@ -0,0 +1,92 @@
+// Runs test ROMs headlessly and compares the final screen against a golden
+// image: the ASCII rendering `chip8-headless --ascii` prints, stored next to
+// the ROM as NAME.txt.
+//
+// The Octo sources in tests/roms are assembled on the fly. Set CHIP8_BLESS=1
+// to rewrite their golden images after changing a source.
+use rust_chip8::asm::assemble_file;
+use rust_chip8::chip8::{Chip8, Platform, Quirks, START_ADDR};
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+const TICKS_PER_FRAME: usize = 15;
+
+struct TestRom {
+    file: &'static str,
+    platform: Platform,
+    quirks: Quirks,
+    frames: usize,
+}
+
+impl TestRom {
+    fn new(file: &'static str, platform: Platform, frames: usize) -> Self {
+        Self {
+            file,
+            platform,
+            quirks: platform.default_quirks(),
+            frames,
+        }
+    }
+
+    fn quirks(mut self, quirks: Quirks) -> Self {
+        self.quirks = quirks;
+        self
+    }
+
+    fn check(self) {
+        let dir = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("tests/roms");
+        let path = dir.join(self.file);
+        let rom = assemble_file(&path, self.platform).unwrap_or_else(|err| panic!("{}", err));
+
+        let mut chip8 = Chip8::new(self.platform, self.quirks);
+        chip8.set_seed(0);
+        chip8.load(&rom, START_ADDR).unwrap();
+        for _ in 0..self.frames {
+            for _ in 0..TICKS_PER_FRAME {
+                chip8
+                    .tick()
+                    .unwrap_or_else(|err| panic!("{}: {}", self.file, err));
+            }
+            chip8.tick_timers();
+        }
+
+        let screen = screen_to_ascii(&chip8);
+        let golden = path.with_extension("txt");
+        if env::var_os("CHIP8_BLESS").is_some() {
+            fs::write(&golden, &screen).unwrap();
+            return;
+        }
+        let expected = fs::read_to_string(&golden)
+            .unwrap_or_else(|err| panic!("{}: {}", golden.display(), err));
+        assert!(
+            screen == expected,
+            "{} doesn't match {}, it drew:\n{}",
+            self.file,
+            golden.display(),
+            screen
+        );
+    }
+}
+
+fn screen_to_ascii(chip8: &Chip8) -> String {
+    let (screen, width, _) = chip8.get_screen();
+    let mut out = String::new();
+    for row in screen.chunks(width) {
+        out.extend(row.iter().map(|pixel| if *pixel != 0 { '#' } else { '.' }));
+        out.push('\n');
+    }
+    out
+}
+
+#[test]
+fn flags() {
+    TestRom::new("flags.8o", Platform::Chip8, 60)
+        .quirks(Quirks::chip48())
+        .check();
+}
+
+#[test]
+fn super_chip_display() {
+    TestRom::new("display.8o", Platform::SuperChip, 10).check();
+}
@@ -0,0 +1,139 @@
+use rust_chip8::asm::assemble;
+use rust_chip8::chip8::{Chip8, Platform, Quirks, StateError, START_ADDR};
+
+// Offsets into a save state
+const VERSION: usize = 4;
+const STACK_POINTER: usize = 8 + 2 + 2 + 16 + 16 * 2;
+// The random number generator's state, added in version 2
+const RNG_SIZE: usize = 8;
+
+// An XO-CHIP program that touches every part of the machine: a subroutine
+// that stays on the stack, timers, both planes in high resolution and
+// random numbers drawn every time round the loop
+const PROGRAM: &str = "
+    : main
+        hires
+        plane 2
+        v0 := 60 delay := v0
+        v0 := 30 buzzer := v0
+        i := dot
+        sub
+    : sub
+        v1 := random 0x3F
+        v2 := random 0x1F
+        sprite v1 v2 1
+        v3 += 1
+        jump sub
+    : dot 0x80
+";
+
+fn machine() -> Chip8 {
+    let rom = assemble(PROGRAM, Platform::XoChip).unwrap_or_else(|err| panic!("{}", err));
+    let mut chip8 = Chip8::new(Platform::XoChip, Quirks::xo_chip());
+    chip8.set_seed(7);
+    chip8.load(&rom, START_ADDR).unwrap();
+    chip8
+}
+
+fn run(chip8: &mut Chip8, frames: usize) {
+    for _ in 0..frames {
+        for _ in 0..10 {
+            chip8.tick().unwrap();
+        }
+        chip8.tick_timers();
+    }
+}
+
+#[test]
+fn round_trip_mid_program() {
+    let mut original = machine();
+    run(&mut original, 5);
+    let state = original.save_state();
+
+    // Restored into a machine set up for something else entirely
+    let mut restored = Chip8::new(Platform::Chip8, Quirks::cosmac_vip());
+    restored.load_state(&state).unwrap();
+    assert_eq!(restored.save_state(), state);
+    assert_eq!(restored.pc(), original.pc());
+    assert_eq!(restored.index_register(), original.index_register());
+    assert_eq!(restored.registers(), original.registers());
+    assert_eq!(restored.stack(), original.stack());
+    assert_eq!(restored.delay_timer(), 55);
+    assert_eq!(restored.sound_timer(), 25);
+    assert_eq!(restored.get_screen(), original.get_screen());
+    assert_eq!(restored.get_screen().1, 128);
+
+    // Both carry on the same, random numbers included
+    run(&mut original, 5);
+    run(&mut restored, 5);
+    assert_eq!(restored.save_state(), original.save_state());
+}
+
+#[test]
+fn rejected_states_leave_the_machine_alone() {
+    let mut chip8 = machine();
+    run(&mut chip8, 2);
+    let state = chip8.save_state();
+    run(&mut chip8, 1);
+    let before = chip8.save_state();
+
+    for len in [0, 3, VERSION + 1, STACK_POINTER, state.len() - 1] {
+        assert_eq!(
+            chip8.load_state(&state[..len]),
+            Err(StateError::Truncated),
+            "{} bytes",
+            len
+        );
+    }
+
+    let mut bad = state.clone();
+    bad[..4].copy_from_slice(b"NES\x1A");
+    assert_eq!(chip8.load_state(&bad), Err(StateError::BadMagic));
+
+    for version in [0, 3, 0xFFFF] {
+        let mut bad = state.clone();
+        bad[VERSION..VERSION + 2].copy_from_slice(&u16::to_le_bytes(version));
+        assert_eq!(
+            chip8.load_state(&bad),
+            Err(StateError::UnsupportedVersion(version))
+        );
+    }
+
+    let mut bad = state.clone();
+    bad[STACK_POINTER] = 17;
+    assert_eq!(
+        chip8.load_state(&bad),
+        Err(StateError::Invalid("stack pointer"))
+    );
+
+    assert_eq!(chip8.save_state(), before);
+}
+
+#[test]
+fn version_1_states_load() {
+    let mut chip8 = machine();
+    run(&mut chip8, 3);
+    let mut state = chip8.save_state();
+    // Version 1 ended before the random number generator
+    state.truncate(state.len() - RNG_SIZE);
+    state[VERSION..VERSION + 2].copy_from_slice(&1u16.to_le_bytes());
+
+    let mut restored = machine();
+    restored.load_state(&state).unwrap();
+    assert_eq!(restored.pc(), chip8.pc());
+    assert_eq!(restored.registers(), chip8.registers());
+    assert_eq!(restored.get_screen(), chip8.get_screen());
+
+    // The machine keeps its own, still unused, generator and writes
+    // version 2 states with it
+    let fresh = machine();
+    let rewritten = restored.save_state();
+    assert_eq!(
+        &rewritten[..VERSION + 2],
+        &chip8.save_state()[..VERSION + 2]
+    );
+    assert_eq!(
+        &rewritten[rewritten.len() - RNG_SIZE..],
+        &fresh.save_state()[rewritten.len() - RNG_SIZE..]
+    );
+}